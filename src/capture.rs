@@ -0,0 +1,251 @@
+//! Framebuffer readback and PNG export.
+
+use std::path::Path;
+
+use image::RgbaImage;
+use wgpu::TextureFormat;
+
+use crate::context::{RenderContext, RenderTarget};
+use crate::scenario::Scenario;
+
+impl RenderContext {
+    /// Renders the scenarios and reads the frame back as an 8-bit RGBA image.
+    ///
+    /// The offscreen target is read directly. A surface texture can't be read
+    /// back, so the frame is rendered once more into a texture of the same
+    /// format and size.
+    ///
+    /// Fails if the target format is not supported, see [`to_rgba8`].
+    pub(crate) fn capture_frame(
+        &self,
+        scenarios: &[Box<dyn Scenario>],
+    ) -> Result<RgbaImage, String> {
+        match &self.target {
+            RenderTarget::Offscreen { texture } => {
+                self.render_frame(scenarios);
+                self.read_texture(texture)
+            }
+            RenderTarget::Surface { .. } => {
+                let size = self.size();
+                let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Capture Target"),
+                    size: wgpu::Extent3d {
+                        width: size.width,
+                        height: size.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: self.swapchain_format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                    view_formats: &[],
+                });
+                self.render_to(&texture, scenarios);
+                self.read_texture(&texture)
+            }
+        }
+    }
+
+    /// Copies the texture into a mappable buffer and converts it to RGBA.
+    fn read_texture(&self, texture: &wgpu::Texture) -> Result<RgbaImage, String> {
+        let (width, height) = (texture.width(), texture.height());
+        let format = texture.format();
+        let bytes_per_pixel = format.describe().block_size as u32;
+
+        // Rows of a texture copy must be padded to a fixed alignment
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        // Wait for the copy and the mapping to finish
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("Failed to map the capture buffer")
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        let converted = slice
+            .get_mapped_range()
+            .chunks(padded_bytes_per_row as usize)
+            .try_for_each(|row| {
+                pixels.extend(to_rgba8(format, &row[..unpadded_bytes_per_row as usize])?);
+                Ok::<_, String>(())
+            });
+        buffer.unmap();
+        converted?;
+
+        Ok(RgbaImage::from_raw(width, height, pixels).expect("Capture has an unexpected size"))
+    }
+}
+
+/// Converts a row of texels of the given format to 8-bit RGBA.
+///
+/// Supports the 8-bit RGBA and BGRA formats, `Rgb10a2Unorm` and
+/// `Rgba16Float`, which covers the usual swapchain formats.
+fn to_rgba8(format: TextureFormat, row: &[u8]) -> Result<Vec<u8>, String> {
+    Ok(match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => row.to_vec(),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => row
+            .chunks_exact(4)
+            .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]])
+            .collect(),
+        TextureFormat::Rgb10a2Unorm => row
+            .chunks_exact(4)
+            .flat_map(|texel| {
+                let bits = u32::from_le_bytes(texel.try_into().unwrap());
+                let channel = |shift: u32, max: u32| {
+                    (((bits >> shift) & max) as f32 / max as f32 * 255.0).round() as u8
+                };
+                [
+                    channel(0, 0x3ff),
+                    channel(10, 0x3ff),
+                    channel(20, 0x3ff),
+                    channel(30, 0x3),
+                ]
+            })
+            .collect(),
+        TextureFormat::Rgba16Float => row
+            .chunks_exact(2)
+            .map(|half| {
+                let value = f16_to_f32(u16::from_le_bytes([half[0], half[1]]));
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            })
+            .collect(),
+        other => return Err(format!("Capturing {other:?} frames is not supported")),
+    })
+}
+
+/// Decodes an IEEE 754 half-precision float.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Saves an image as PNG.
+pub(crate) fn save_png(image: &RgbaImage, path: &Path) -> Result<(), String> {
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalSize;
+
+    #[test]
+    fn swizzles_bgra() {
+        let row = [1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(
+            to_rgba8(TextureFormat::Bgra8Unorm, &row).unwrap(),
+            [3, 2, 1, 4, 7, 6, 5, 8]
+        );
+        assert_eq!(to_rgba8(TextureFormat::Rgba8UnormSrgb, &row).unwrap(), row);
+    }
+
+    #[test]
+    fn converts_wide_formats() {
+        // Red at full, green at a third, blue at zero and alpha at two thirds
+        let bits: u32 = 0x3ff | (341 << 10) | (2 << 30);
+        assert_eq!(
+            to_rgba8(TextureFormat::Rgb10a2Unorm, &bits.to_le_bytes()).unwrap(),
+            [255, 85, 0, 170]
+        );
+
+        // 1.0, 0.5, -2.0 (clamped) and 65504.0 (clamped)
+        let halfs = [0x3c00u16, 0x3800, 0xc000, 0x7bff];
+        let row = halfs
+            .iter()
+            .flat_map(|h| h.to_le_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            to_rgba8(TextureFormat::Rgba16Float, &row).unwrap(),
+            [255, 128, 0, 255]
+        );
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn rejects_unsupported_formats() {
+        assert_eq!(
+            to_rgba8(TextureFormat::R8Unorm, &[0; 4]),
+            Err("Capturing R8Unorm frames is not supported".to_owned())
+        );
+    }
+
+    #[test]
+    fn strips_row_padding() {
+        let Some(context) = RenderContext::test_context(PhysicalSize::new(16, 16)) else {
+            return;
+        };
+
+        // 5 texels are 20 bytes, so the copied rows are padded to 256 bytes
+        let size = wgpu::Extent3d {
+            width: 5,
+            height: 3,
+            depth_or_array_layers: 1,
+        };
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Padded Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let texels = (0..5 * 3 * 4).map(|i| i as u8).collect::<Vec<_>>();
+        context.queue.write_texture(
+            texture.as_image_copy(),
+            &texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(5 * 4),
+                rows_per_image: None,
+            },
+            size,
+        );
+
+        let image = context.read_texture(&texture).unwrap();
+        assert_eq!(image.dimensions(), (5, 3));
+        assert_eq!(image.into_raw(), texels);
+    }
+}
@@ -0,0 +1,63 @@
+//! Color management between the shaders and the render target.
+//!
+//! Colors are authored in sRGB and decoded to linear in the shaders. Whether
+//! they are encoded again by the render target or by the shader depends on
+//! the target format, so the shaders include the shared conversions of
+//! `shaders/color.wgsl` and are composed with `TARGET_IS_SRGB` defined for
+//! sRGB targets (see [`crate::shader`]). This keeps the colors identical on
+//! sRGB surfaces (usual natively) and linear ones (usual on the web).
+
+use wgpu::TextureFormat;
+
+/// Whether writes to the format are sRGB encoded by the GPU.
+pub(crate) fn is_srgb(format: TextureFormat) -> bool {
+    format.describe().srgb
+}
+
+/// Encodes a linear color component to sRGB, e.g. for vertex colors of
+/// glTF files, which are linear.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario;
+    use crate::RenderContext;
+    use winit::dpi::PhysicalSize;
+
+    fn capture(format: TextureFormat) -> Option<image::RgbaImage> {
+        let context = RenderContext::test_context_with_format(format, PhysicalSize::new(64, 64))?;
+        let scenarios = scenario::select(Some(&["with_color".to_owned()]))
+            .unwrap()
+            .iter()
+            .map(|entry| entry.build(&context))
+            .collect::<Vec<_>>();
+        Some(context.capture_frame(&scenarios).unwrap())
+    }
+
+    #[test]
+    fn srgb_and_linear_targets_match() {
+        let (Some(linear), Some(srgb)) = (
+            capture(TextureFormat::Rgba8Unorm),
+            capture(TextureFormat::Rgba8UnormSrgb),
+        ) else {
+            return;
+        };
+
+        let max_difference = linear
+            .pixels()
+            .zip(srgb.pixels())
+            .flat_map(|(a, b)| a.0.into_iter().zip(b.0).map(|(a, b)| a.abs_diff(b)))
+            .max()
+            .unwrap();
+        // Blending and rounding happen in different spaces
+        assert!(max_difference <= 8, "differs by up to {max_difference}");
+    }
+}
@@ -0,0 +1,209 @@
+//! Location-compacting rewriter, a workaround for the attribute gap bug.
+//!
+//! The GL backend fails with `INVALID_OPERATION` if the vertex attribute
+//! locations of a pipeline have gaps (e.g. `OnlyPos` uses 0 and 2-5). This
+//! pass renumbers the `@location`s of the vertex inputs to a dense range,
+//! both in the naga module and in the `VertexAttribute`s, so `OnlyPos` ends
+//! up with locations 0-4.
+//!
+//! The pass is opt-in via the `compact-locations` option.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+use crate::reflect::{self, LayoutError};
+use crate::RenderContext;
+
+/// Renumbers the vertex input locations of `entry_point` and `attributes`.
+///
+/// Returns the mapping from the old to the new locations.
+pub(crate) fn compact_locations(
+    module: &mut naga::Module,
+    entry_point: &str,
+    attributes: &mut [Vec<wgpu::VertexAttribute>],
+) -> Result<BTreeMap<u32, u32>, LayoutError> {
+    let inputs = reflect::vertex_inputs(module, entry_point)?;
+
+    let used = inputs
+        .iter()
+        .map(|input| input.location)
+        .chain(attributes.iter().flatten().map(|attr| attr.shader_location))
+        .collect::<BTreeSet<_>>();
+    let mapping = used.into_iter().zip(0..).collect::<BTreeMap<u32, u32>>();
+
+    for attr in attributes.iter_mut().flatten() {
+        attr.shader_location = mapping[&attr.shader_location];
+    }
+
+    let naga::Module {
+        types,
+        functions,
+        entry_points,
+        ..
+    } = module;
+    let function = &mut entry_points
+        .iter_mut()
+        .find(|ep| ep.name == entry_point && ep.stage == naga::ShaderStage::Vertex)
+        .ok_or_else(|| LayoutError::MissingEntryPoint(entry_point.to_owned()))?
+        .function;
+
+    let mut replaced = Vec::new();
+    for arg in &mut function.arguments {
+        if let Some(binding) = &mut arg.binding {
+            remap_binding(binding, &mapping);
+            continue;
+        }
+
+        // Types are immutable, so insert a renumbered copy of the struct
+        let old_ty = arg.ty;
+        let mut ty = types[old_ty].clone();
+        let naga::TypeInner::Struct { members, .. } = &mut ty.inner else {
+            continue;
+        };
+        for binding in members.iter_mut().filter_map(|m| m.binding.as_mut()) {
+            remap_binding(binding, &mapping);
+        }
+        let new_ty = types.insert(ty, types.get_span(old_ty));
+
+        arg.ty = new_ty;
+        replaced.push((old_ty, new_ty));
+    }
+
+    // Replace all uses of the old structs, including the shared helpers the
+    // inputs are passed to (e.g. `instance_matrix`)
+    for (old_ty, new_ty) in replaced {
+        replace_type(function, old_ty, new_ty);
+        for (_, function) in functions.iter_mut() {
+            replace_type(function, old_ty, new_ty);
+        }
+    }
+
+    Ok(mapping)
+}
+
+fn replace_type(
+    function: &mut naga::Function,
+    old_ty: naga::Handle<naga::Type>,
+    new_ty: naga::Handle<naga::Type>,
+) {
+    for arg in &mut function.arguments {
+        if arg.ty == old_ty {
+            arg.ty = new_ty;
+        }
+    }
+    if let Some(result) = &mut function.result {
+        if result.ty == old_ty {
+            result.ty = new_ty;
+        }
+    }
+    for (_, local) in function.local_variables.iter_mut() {
+        if local.ty == old_ty {
+            local.ty = new_ty;
+        }
+    }
+    for (_, expr) in function.expressions.iter_mut() {
+        if let naga::Expression::Compose { ty, .. } = expr {
+            if *ty == old_ty {
+                *ty = new_ty;
+            }
+        }
+    }
+}
+
+fn remap_binding(binding: &mut naga::Binding, mapping: &BTreeMap<u32, u32>) {
+    if let naga::Binding::Location { location, .. } = binding {
+        *location = mapping[location];
+    }
+}
+
+/// Writes a naga module back to WGSL.
+pub(crate) fn to_wgsl(module: &naga::Module) -> Result<String, String> {
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(module)
+        .map_err(|e| format!("rewritten module is invalid: {e}"))?;
+
+    naga::back::wgsl::write_string(module, &info, naga::back::wgsl::WriterFlags::empty())
+        .map_err(|e| format!("failed to write WGSL: {e}"))
+}
+
+/// Applies the pass if enabled in the context, returning the shader source
+/// to use together with `attributes`.
+pub(crate) fn compact_if_enabled<'a>(
+    context: &RenderContext,
+    source: &'a str,
+    module: &mut naga::Module,
+    entry_point: &str,
+    attributes: &mut [Vec<wgpu::VertexAttribute>],
+) -> Result<Cow<'a, str>, String> {
+    if !context.compact_locations {
+        return Ok(Cow::Borrowed(source));
+    }
+
+    let mapping = compact_locations(module, entry_point, attributes).map_err(|e| e.to_string())?;
+    log::info!("Compacted vertex locations: {mapping:?}");
+
+    to_wgsl(module).map(Cow::Owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader;
+    use crate::webgl2::{self, Webgl2Hazard};
+
+    #[test]
+    fn compacts_only_pos() {
+        let mut module = reflect::parse_wgsl(
+            &shader::compose(include_str!("only_pos/shader.wgsl"), &Default::default()).unwrap(),
+        )
+        .unwrap();
+        let mut attributes = vec![
+            wgpu::vertex_attr_array![0 => Float32x3].to_vec(),
+            wgpu::vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4]
+                .to_vec(),
+        ];
+
+        let before = webgl2::validate_vertex_state(&buffers(&attributes), "vs_main", &module);
+        assert_eq!(
+            before,
+            [Webgl2Hazard::NonContiguousLocations { missing: vec![1] }]
+        );
+
+        let mapping = compact_locations(&mut module, "vs_main", &mut attributes).unwrap();
+        assert_eq!(
+            mapping.into_iter().collect::<Vec<_>>(),
+            [(0, 0), (2, 1), (3, 2), (4, 3), (5, 4)]
+        );
+        let locations = attributes
+            .iter()
+            .flatten()
+            .map(|attr| attr.shader_location)
+            .collect::<Vec<_>>();
+        assert_eq!(locations, [0, 1, 2, 3, 4]);
+
+        // The rewritten shader must still parse and match the new layouts
+        let module = reflect::parse_wgsl(&to_wgsl(&module).unwrap()).unwrap();
+        reflect::check_vertex_layouts(&module, "vs_main", &buffers(&attributes)).unwrap();
+        assert_eq!(
+            webgl2::validate_vertex_state(&buffers(&attributes), "vs_main", &module),
+            []
+        );
+    }
+
+    fn buffers(attributes: &[Vec<wgpu::VertexAttribute>]) -> Vec<wgpu::VertexBufferLayout<'_>> {
+        attributes
+            .iter()
+            .map(|attributes| wgpu::VertexBufferLayout {
+                array_stride: attributes
+                    .iter()
+                    .map(|attr| attr.offset + attr.format.size())
+                    .max()
+                    .unwrap_or(0),
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes,
+            })
+            .collect()
+    }
+}
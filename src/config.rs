@@ -0,0 +1,286 @@
+//! Adapter, backend and power-preference selection.
+//!
+//! The bug depends on the backend, so the adapter can be chosen explicitly
+//! instead of taking whichever one wgpu picks first. Each setting is read from
+//! the options (see [`crate::options`]) or, on native, from the environment
+//! variables also used by the wgpu examples:
+//!
+//! - `--backend` / `WGPU_BACKEND`: comma separated list of `vulkan`, `gl`,
+//!   `metal`, `dx12`, `dx11` and `webgpu`, e.g. `vulkan,gl`
+//! - `--adapter-name` / `WGPU_ADAPTER_NAME`: case-insensitive substring of the
+//!   adapter name, e.g. `llvmpipe`
+//! - `--power-preference`: `low` or `high`
+//! - `--fallback-adapter`: force the software fallback adapter
+//! - `--profile`: the features and limits requested from the device, see
+//!   [`DeviceProfile`]
+//! - `--srgb`, `--surface-format`, `--present-mode`: how to configure the
+//!   surface, see [`SurfacePolicy`]
+
+use crate::options::Options;
+use crate::surface::{self, SrgbPreference, SurfacePolicy};
+
+/// How to select the adapter of a [`crate::RenderContext`].
+#[derive(Debug, Clone)]
+pub(crate) struct RenderConfig {
+    /// Backends the instance may use.
+    pub(crate) backends: wgpu::Backends,
+    pub(crate) power_preference: wgpu::PowerPreference,
+    /// Only use the software fallback adapter.
+    pub(crate) force_fallback_adapter: bool,
+    /// Substring the adapter name must contain, ignoring case.
+    pub(crate) adapter_name: Option<String>,
+    /// Features and limits to request from the device.
+    pub(crate) profile: DeviceProfile,
+    /// How to choose the surface format and present mode.
+    pub(crate) surface: SurfacePolicy,
+}
+
+/// A named set of features and limits to request from the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum DeviceProfile {
+    /// No features and the WebGL2 limits, with the adapter's texture
+    /// resolution so the swapchain fits.
+    #[default]
+    Webgl2,
+    /// No features and the downlevel limits, with the adapter's texture
+    /// resolution.
+    Downlevel,
+    /// No features and the default (WebGPU) limits.
+    Default,
+    /// All features and limits of the adapter.
+    AdapterMax,
+}
+
+impl DeviceProfile {
+    pub(crate) const ALL: [Self; 4] = [
+        Self::Webgl2,
+        Self::Downlevel,
+        Self::Default,
+        Self::AdapterMax,
+    ];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Webgl2 => "webgl2",
+            Self::Downlevel => "downlevel",
+            Self::Default => "default",
+            Self::AdapterMax => "adapter-max",
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|profile| profile.name() == name.to_lowercase())
+            .ok_or_else(|| {
+                let names = Self::ALL.map(Self::name).join(", ");
+                format!("Unknown device profile `{name}`, available: {names}")
+            })
+    }
+
+    /// The features to request from the adapter.
+    pub(crate) fn features(self, adapter: &wgpu::Adapter) -> wgpu::Features {
+        match self {
+            Self::Webgl2 | Self::Downlevel | Self::Default => wgpu::Features::empty(),
+            Self::AdapterMax => adapter.features(),
+        }
+    }
+
+    /// The limits to request from the adapter.
+    pub(crate) fn limits(self, adapter: &wgpu::Adapter) -> wgpu::Limits {
+        match self {
+            Self::Webgl2 => {
+                wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+            }
+            Self::Downlevel => {
+                wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits())
+            }
+            Self::Default => wgpu::Limits::default(),
+            Self::AdapterMax => adapter.limits(),
+        }
+    }
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            adapter_name: None,
+            profile: DeviceProfile::default(),
+            surface: SurfacePolicy::default(),
+        }
+    }
+}
+
+impl RenderConfig {
+    /// Builds the config from the options, falling back to the environment
+    /// variables for unset options.
+    pub(crate) fn from_options(options: &Options) -> Result<Self, String> {
+        let mut config = Self::default();
+
+        if let Some(backends) = options.backend.clone().or_else(|| env_var("WGPU_BACKEND")) {
+            config.backends = parse_backends(&backends)?;
+        }
+        if let Some(power_preference) = &options.power_preference {
+            config.power_preference = parse_power_preference(power_preference)?;
+        }
+        if let Some(profile) = &options.profile {
+            config.profile = DeviceProfile::parse(profile)?;
+        }
+        if let Some(srgb) = &options.srgb {
+            config.surface.srgb = SrgbPreference::parse(srgb)?;
+        }
+        if let Some(format) = &options.surface_format {
+            config.surface.format = Some(surface::parse_format(format)?);
+        }
+        if let Some(present_mode) = &options.present_mode {
+            config.surface.present_mode = Some(surface::parse_present_mode(present_mode)?);
+        }
+        config.force_fallback_adapter = options.fallback_adapter;
+        config.adapter_name = options
+            .adapter_name
+            .clone()
+            .or_else(|| env_var("WGPU_ADAPTER_NAME"))
+            .filter(|name| !name.is_empty());
+
+        Ok(config)
+    }
+
+    /// Creates an instance limited to the configured backends.
+    pub(crate) fn create_instance(&self) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: self.backends,
+            dx12_shader_compiler: Default::default(),
+        })
+    }
+
+    /// Whether the adapter matches the name filter.
+    pub(crate) fn matches_name(&self, info: &wgpu::AdapterInfo) -> bool {
+        self.adapter_name.as_ref().map_or(true, |name| {
+            info.name.to_lowercase().contains(&name.to_lowercase())
+        })
+    }
+
+    /// Finds an adapter matching the config.
+    ///
+    /// With a name filter, the adapters are enumerated and the first matching
+    /// one is taken. That isn't possible on the web, where the requested
+    /// adapter is only checked against the filter.
+    pub(crate) async fn request_adapter(
+        &self,
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface>,
+    ) -> Option<wgpu::Adapter> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.adapter_name.is_some() {
+            return instance.enumerate_adapters(self.backends).find(|adapter| {
+                let info = adapter.get_info();
+                self.matches_name(&info)
+                    && (!self.force_fallback_adapter || info.device_type == wgpu::DeviceType::Cpu)
+                    && compatible_surface.map_or(true, |s| adapter.is_surface_supported(s))
+            });
+        }
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: self.power_preference,
+                force_fallback_adapter: self.force_fallback_adapter,
+                compatible_surface,
+            })
+            .await?;
+
+        if !self.matches_name(&adapter.get_info()) {
+            log::warn!(
+                "Adapter `{}` doesn't match the name filter {:?}",
+                adapter.get_info().name,
+                self.adapter_name,
+            );
+            return None;
+        }
+        Some(adapter)
+    }
+}
+
+/// Reads an environment variable, never set on the web.
+fn env_var(key: &str) -> Option<String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return std::env::var(key).ok();
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = key;
+        None
+    }
+}
+
+/// Parses a comma separated list of backend names.
+fn parse_backends(list: &str) -> Result<wgpu::Backends, String> {
+    let mut backends = wgpu::Backends::empty();
+    for name in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        backends |= match name.to_lowercase().as_str() {
+            "vulkan" | "vk" => wgpu::Backends::VULKAN,
+            "gl" | "gles" | "opengl" | "webgl" | "webgl2" => wgpu::Backends::GL,
+            "metal" | "mtl" => wgpu::Backends::METAL,
+            "dx12" | "d3d12" => wgpu::Backends::DX12,
+            "dx11" | "d3d11" => wgpu::Backends::DX11,
+            "webgpu" | "browser_webgpu" => wgpu::Backends::BROWSER_WEBGPU,
+            "primary" => wgpu::Backends::PRIMARY,
+            "secondary" => wgpu::Backends::SECONDARY,
+            "all" => wgpu::Backends::all(),
+            _ => return Err(format!("Unknown backend `{name}`")),
+        };
+    }
+
+    if backends.is_empty() {
+        return Err("No backend given".to_owned());
+    }
+    Ok(backends)
+}
+
+fn parse_power_preference(name: &str) -> Result<wgpu::PowerPreference, String> {
+    match name.to_lowercase().as_str() {
+        "low" | "low-power" => Ok(wgpu::PowerPreference::LowPower),
+        "high" | "high-performance" => Ok(wgpu::PowerPreference::HighPerformance),
+        _ => Err(format!("Unknown power preference `{name}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_backend_lists() {
+        assert_eq!(parse_backends("gl").unwrap(), wgpu::Backends::GL);
+        assert_eq!(
+            parse_backends("Vulkan, dx12").unwrap(),
+            wgpu::Backends::VULKAN | wgpu::Backends::DX12
+        );
+        assert!(parse_backends("").is_err());
+        assert!(parse_backends("glide").is_err());
+    }
+
+    #[test]
+    fn parses_profiles() {
+        for profile in DeviceProfile::ALL {
+            assert_eq!(DeviceProfile::parse(profile.name()), Ok(profile));
+        }
+        assert!(DeviceProfile::parse("webgl1").is_err());
+    }
+
+    #[test]
+    fn options_take_precedence() {
+        let options = Options {
+            backend: Some("metal".to_owned()),
+            power_preference: Some("low".to_owned()),
+            adapter_name: Some("Apple".to_owned()),
+            ..Options::default()
+        };
+        let config = RenderConfig::from_options(&options).unwrap();
+
+        assert_eq!(config.backends, wgpu::Backends::METAL);
+        assert_eq!(config.power_preference, wgpu::PowerPreference::LowPower);
+        assert_eq!(config.adapter_name.as_deref(), Some("Apple"));
+    }
+}
@@ -0,0 +1,477 @@
+//! GPU device setup and render targets.
+
+use log::{info, warn};
+use wgpu::TextureFormat;
+use winit::dpi::PhysicalSize;
+use winit::window::Window;
+
+use crate::config::{DeviceProfile, RenderConfig};
+use crate::errors::{ErrorLog, Phase};
+use crate::scenario::Scenario;
+
+/// Where the frames are rendered to.
+pub(crate) enum RenderTarget {
+    /// The surface of a window.
+    Surface {
+        surface: wgpu::Surface,
+        config: wgpu::SurfaceConfiguration,
+    },
+    /// An offscreen texture, e.g. for tests without a window.
+    Offscreen { texture: wgpu::Texture },
+}
+
+/// The view transformation shared by all scenarios, as `View` in WGSL.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ViewUniform {
+    /// Scale of clip space positions to keep the aspect ratio.
+    scale: [f32; 2],
+    _padding: [f32; 2],
+}
+
+pub(crate) struct RenderContext {
+    pub(crate) adapter_info: wgpu::AdapterInfo,
+    /// The profile the device was requested with.
+    pub(crate) profile: DeviceProfile,
+    pub(crate) downlevel: wgpu::DownlevelCapabilities,
+    pub(crate) target: RenderTarget,
+    pub(crate) device: wgpu::Device,
+    pub(crate) queue: wgpu::Queue,
+    pub(crate) swapchain_format: wgpu::TextureFormat,
+    /// Uniform buffer of the [`ViewUniform`], updated on resize.
+    view_buffer: wgpu::Buffer,
+    /// Layout of the bind group of the view uniform, see
+    /// [`RenderContext::create_view_bind_group`].
+    pub(crate) view_bind_group_layout: wgpu::BindGroupLayout,
+    /// The wgpu errors captured on the device.
+    pub(crate) errors: ErrorLog,
+    /// Whether scenarios apply the location-compacting workaround.
+    pub(crate) compact_locations: bool,
+    /// Directory to load the shaders from instead of the embedded ones, see
+    /// [`crate::hot_reload`].
+    pub(crate) shader_dir: Option<std::path::PathBuf>,
+}
+impl RenderContext {
+    pub(crate) async fn new(window: &Window, config: &RenderConfig) -> Self {
+        let size = window.inner_size();
+
+        let instance = config.create_instance();
+        info!("{instance:?}");
+        let surface_result = unsafe { instance.create_surface(window) };
+        let surface = surface_result.expect("Failed to create surface");
+        // Request an adapter which can render to our surface
+        let adapter = config
+            .request_adapter(&instance, Some(&surface))
+            .await
+            .unwrap_or_else(|| panic!("Failed to find an adapter matching {config:?}"));
+        info!("{:?}", adapter.get_info());
+
+        let profile = config.profile;
+        let (device, queue) = Self::request_device(&adapter, profile).await;
+
+        let caps = surface.get_capabilities(&adapter);
+        info!("{caps:?}");
+
+        let swapchain_format = config
+            .surface
+            .choose_format(&caps.formats)
+            .expect("No supported swap-chain texture formats");
+        let present_mode = config
+            .surface
+            .choose_present_mode(&caps.present_modes)
+            .expect("No supported present modes");
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: swapchain_format,
+            width: size.width,
+            height: size.height,
+            present_mode,
+            alpha_mode: caps.alpha_modes[0],
+            view_formats: vec![swapchain_format],
+        };
+
+        surface.configure(&device, &config);
+
+        Self::from_parts(
+            &adapter,
+            profile,
+            RenderTarget::Surface { surface, config },
+            device,
+            queue,
+            swapchain_format,
+        )
+    }
+
+    /// Creates a context rendering into an offscreen texture.
+    ///
+    /// Falls back to a software adapter if there is no hardware one matching
+    /// the config. Returns `None` if there is no matching adapter at all.
+    pub(crate) async fn new_headless(
+        config: &RenderConfig,
+        format: TextureFormat,
+        size: PhysicalSize<u32>,
+    ) -> Option<Self> {
+        let instance = config.create_instance();
+        info!("{instance:?}");
+
+        let mut adapter = config.request_adapter(&instance, None).await;
+        if adapter.is_none() && !config.force_fallback_adapter {
+            let fallback = RenderConfig {
+                force_fallback_adapter: true,
+                ..config.clone()
+            };
+            adapter = fallback.request_adapter(&instance, None).await;
+        }
+        let adapter = adapter?;
+        info!("{:?}", adapter.get_info());
+
+        Some(Self::from_adapter(&adapter, config.profile, format, size).await)
+    }
+
+    /// A headless `Rgba8Unorm` context of the default config for tests.
+    ///
+    /// See [`Self::test_context_with_format`].
+    #[cfg(test)]
+    pub(crate) fn test_context(size: PhysicalSize<u32>) -> Option<Self> {
+        Self::test_context_with_format(TextureFormat::Rgba8Unorm, size)
+    }
+
+    /// A headless context of the default config for tests.
+    ///
+    /// Returns `None` to skip the test if there is no adapter, unless `CI` is
+    /// set, where a missing adapter fails the test instead of passing
+    /// silently.
+    #[cfg(test)]
+    pub(crate) fn test_context_with_format(
+        format: TextureFormat,
+        size: PhysicalSize<u32>,
+    ) -> Option<Self> {
+        let context =
+            pollster::block_on(Self::new_headless(&RenderConfig::default(), format, size));
+        if context.is_none() {
+            assert!(std::env::var_os("CI").is_none(), "No adapter available");
+            eprintln!("No adapter available, skipping");
+        }
+        context
+    }
+
+    /// Creates a context on the given adapter rendering into an offscreen
+    /// texture.
+    pub(crate) async fn from_adapter(
+        adapter: &wgpu::Adapter,
+        profile: DeviceProfile,
+        format: TextureFormat,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        let (device, queue) = Self::request_device(adapter, profile).await;
+
+        let texture = Self::create_offscreen_texture(&device, format, size);
+
+        Self::from_parts(
+            adapter,
+            profile,
+            RenderTarget::Offscreen { texture },
+            device,
+            queue,
+            format,
+        )
+    }
+
+    fn from_parts(
+        adapter: &wgpu::Adapter,
+        profile: DeviceProfile,
+        target: RenderTarget,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        swapchain_format: wgpu::TextureFormat,
+    ) -> Self {
+        let view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("View Uniform Buffer"),
+            size: std::mem::size_of::<ViewUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let view_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("View Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let errors = ErrorLog::default();
+        errors.install(&device);
+
+        let context = Self {
+            adapter_info: adapter.get_info(),
+            profile,
+            downlevel: adapter.get_downlevel_capabilities(),
+            target,
+            device,
+            queue,
+            swapchain_format,
+            view_buffer,
+            view_bind_group_layout,
+            errors,
+            compact_locations: false,
+            shader_dir: None,
+        };
+        context.update_view();
+        context
+    }
+
+    fn create_offscreen_texture(
+        device: &wgpu::Device,
+        format: TextureFormat,
+        size: PhysicalSize<u32>,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // The texture is rendered to and then copied out.
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Creates the logical device and command queue.
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+        profile: DeviceProfile,
+    ) -> (wgpu::Device, wgpu::Queue) {
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: profile.features(adapter),
+                    limits: profile.limits(adapter),
+                },
+                None,
+            )
+            .await
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Failed to create device with the `{}` profile: {e}",
+                    profile.name()
+                )
+            });
+        info!("{device:?}");
+
+        (device, queue)
+    }
+
+    /// The size of the render target in pixels.
+    pub(crate) fn size(&self) -> PhysicalSize<u32> {
+        match &self.target {
+            RenderTarget::Surface { config, .. } => PhysicalSize::new(config.width, config.height),
+            RenderTarget::Offscreen { texture } => {
+                PhysicalSize::new(texture.width(), texture.height())
+            }
+        }
+    }
+
+    /// The present mode of the surface, `None` when rendering offscreen.
+    pub(crate) fn present_mode(&self) -> Option<wgpu::PresentMode> {
+        match &self.target {
+            RenderTarget::Surface { config, .. } => Some(config.present_mode),
+            RenderTarget::Offscreen { .. } => None,
+        }
+    }
+
+    /// Resizes the render target.
+    ///
+    /// Zero sizes (e.g. while minimized) are ignored, as a surface can't be
+    /// configured with them.
+    pub(crate) fn resize(&mut self, size: PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 || size == self.size() {
+            return;
+        }
+        info!("Resizing to {}x{}", size.width, size.height);
+
+        match &mut self.target {
+            RenderTarget::Surface { surface, config } => {
+                config.width = size.width;
+                config.height = size.height;
+                surface.configure(&self.device, config);
+            }
+            RenderTarget::Offscreen { texture } => {
+                *texture =
+                    Self::create_offscreen_texture(&self.device, self.swapchain_format, size);
+            }
+        }
+
+        self.update_view();
+    }
+
+    /// Writes the view uniform for the current size.
+    fn update_view(&self) {
+        let size = self.size();
+        let (width, height) = (size.width.max(1) as f32, size.height.max(1) as f32);
+
+        // Fit the unit square of clip space into the target
+        let min = width.min(height);
+        let view = ViewUniform {
+            scale: [min / width, min / height],
+            _padding: [0.0; 2],
+        };
+        self.queue
+            .write_buffer(&self.view_buffer, 0, bytemuck::bytes_of(&view));
+    }
+
+    /// Creates a bind group of the view uniform for `view_bind_group_layout`.
+    pub(crate) fn create_view_bind_group(&self) -> wgpu::BindGroup {
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("View Bind Group"),
+            layout: &self.view_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: self.view_buffer.as_entire_binding(),
+            }],
+        })
+    }
+
+    /// Renders the scenarios into the next frame of the target.
+    pub(crate) fn render_frame(&self, scenarios: &[Box<dyn Scenario>]) {
+        match &self.target {
+            RenderTarget::Surface { surface, config } => {
+                // Get next frame
+                let frame = match surface.get_current_texture() {
+                    Ok(frame) => frame,
+                    // Reconfigure the surface and try again with the next frame
+                    Err(err @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                        warn!("Reconfiguring surface: {err}");
+                        surface.configure(&self.device, config);
+                        return;
+                    }
+                    Err(wgpu::SurfaceError::Timeout) => {
+                        warn!("Timeout while acquiring the next frame");
+                        return;
+                    }
+                    Err(err @ wgpu::SurfaceError::OutOfMemory) => {
+                        panic!("Failed to acquire next swap chain texture: {err}")
+                    }
+                };
+
+                self.render_to(&frame.texture, scenarios);
+
+                frame.present();
+            }
+            RenderTarget::Offscreen { texture } => {
+                self.render_to(texture, scenarios);
+            }
+        }
+    }
+
+    /// Renders the scenarios into the given texture.
+    pub(crate) fn render_to(&self, texture: &wgpu::Texture, scenarios: &[Box<dyn Scenario>]) {
+        // Get frame texture view
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // All scenarios share a single bundle, recorded in order
+        let bundle = self.capture_errors(None, Phase::BundleEncoding, || {
+            let mut secondary_pass =
+                self.device
+                    .create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+                        label: None,
+                        color_formats: &[Some(self.swapchain_format)],
+                        depth_stencil: None,
+                        sample_count: 1,
+                        multiview: None,
+                    });
+            for scenario in scenarios {
+                scenario.render(&mut secondary_pass);
+            }
+            secondary_pass.finish(&Default::default())
+        });
+
+        self.capture_errors(None, Phase::Submit, || self.submit(&view, &bundle));
+    }
+
+    /// Executes the bundle in a render pass clearing the view.
+    fn submit(&self, view: &wgpu::TextureView, bundle: &wgpu::RenderBundle) {
+        // Create a command encoder (to record draw calls)
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            // Create a render pass
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            pass.execute_bundles([bundle]);
+        }
+
+        // Submit command buffer
+        self.queue.submit(Some(encoder.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::only_pos::OnlyPos;
+    use crate::scenario;
+
+    #[test]
+    fn all_scenarios_render_headless() {
+        let size = PhysicalSize::new(64, 64);
+        let Some(context) = RenderContext::test_context(size) else {
+            return;
+        };
+
+        let scenarios = scenario::registry()
+            .iter()
+            .map(|entry| entry.build(&context))
+            .collect::<Vec<_>>();
+        context.render_frame(&scenarios);
+        context.device.poll(wgpu::Maintain::Wait);
+
+        assert_eq!(context.size(), size);
+        assert!(context.errors.is_empty(), "{}", context.errors.summary());
+    }
+
+    #[test]
+    fn resize_ignores_zero_sizes() {
+        let Some(mut context) = RenderContext::test_context(PhysicalSize::new(64, 64)) else {
+            return;
+        };
+
+        context.resize(PhysicalSize::new(0, 32));
+        assert_eq!(context.size(), PhysicalSize::new(64, 64));
+
+        context.resize(PhysicalSize::new(128, 32));
+        assert_eq!(context.size(), PhysicalSize::new(128, 32));
+
+        // Rendering uses the new target
+        let frame = context
+            .capture_frame(&[Box::new(OnlyPos::new(&context)) as Box<dyn Scenario>])
+            .unwrap();
+        assert_eq!(frame.dimensions(), (128, 32));
+    }
+}
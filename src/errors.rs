@@ -0,0 +1,253 @@
+//! Structured capture of wgpu errors.
+//!
+//! Instead of panicking in the default uncaptured error handler, the errors
+//! of a [`RenderContext`] are collected into its [`ErrorLog`]. Pipeline
+//! creation is wrapped in an error scope per scenario, and each frame in one
+//! scope per phase, so the errors can be attributed to a phase and, while
+//! building, to a scenario.
+//!
+//! Errors of a frame can't be attributed to a scenario: all scenarios are
+//! recorded into one shared render bundle, and wgpu only validates its
+//! commands as a whole when the bundle is finished, so an error scope around
+//! the draws of a single scenario would never catch anything. The sweep
+//! renders each scenario alone as well to narrow such errors down.
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use crate::RenderContext;
+
+/// The phase in which an error occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Phase {
+    /// Building a scenario, i.e. creating its pipeline and buffers.
+    PipelineCreation,
+    /// Encoding the shared render bundle of all scenarios.
+    BundleEncoding,
+    /// Encoding and submitting the render pass of a frame.
+    Submit,
+    /// Outside of any error scope.
+    Uncaptured,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::PipelineCreation => "pipeline creation",
+            Self::BundleEncoding => "bundle encoding",
+            Self::Submit => "submit",
+            Self::Uncaptured => "uncaptured",
+        })
+    }
+}
+
+/// A wgpu error together with where it occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CapturedError {
+    /// The scenario, if the error can be attributed to one, i.e. only during
+    /// pipeline creation.
+    pub(crate) scenario: Option<String>,
+    pub(crate) phase: Phase,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for CapturedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.scenario {
+            Some(scenario) => write!(f, "`{scenario}` during {}: ", self.phase)?,
+            None => write!(f, "During {}: ", self.phase)?,
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The errors captured on a device.
+///
+/// Clones share the same log, so it can be moved into the uncaptured error
+/// handler of the device.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ErrorLog {
+    errors: Arc<Mutex<Vec<CapturedError>>>,
+}
+
+impl ErrorLog {
+    /// Installs a handler collecting all errors outside of error scopes.
+    pub(crate) fn install(&self, device: &wgpu::Device) {
+        let log = self.clone();
+        device.on_uncaptured_error(Box::new(move |error| {
+            log.record(None, Phase::Uncaptured, &error);
+        }));
+    }
+
+    pub(crate) fn record(&self, scenario: Option<&str>, phase: Phase, error: &wgpu::Error) {
+        let error = CapturedError {
+            scenario: scenario.map(str::to_owned),
+            phase,
+            message: error.to_string(),
+        };
+        log::error!("{error}");
+        self.lock().push(error);
+    }
+
+    /// Runs `f` within validation and out-of-memory error scopes, recording
+    /// the errors raised by it.
+    ///
+    /// Natively the errors are recorded before this returns. On the web, the
+    /// scopes resolve asynchronously, so they are recorded later on.
+    pub(crate) fn capture<T>(
+        &self,
+        device: &wgpu::Device,
+        scenario: Option<&str>,
+        phase: Phase,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        // The scopes must be popped even if `f` panics (e.g. in the sweep),
+        // or they would swallow all later errors
+        let result = panic::catch_unwind(AssertUnwindSafe(f));
+        let validation = device.pop_error_scope();
+        let out_of_memory = device.pop_error_scope();
+
+        let log = self.clone();
+        let scenario = scenario.map(str::to_owned);
+        let record = async move {
+            for error in [validation.await, out_of_memory.await]
+                .into_iter()
+                .flatten()
+            {
+                log.record(scenario.as_deref(), phase, &error);
+            }
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        pollster::block_on(record);
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(record);
+
+        result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+
+    /// All errors captured so far.
+    pub(crate) fn errors(&self) -> Vec<CapturedError> {
+        self.lock().clone()
+    }
+
+    /// The errors attributed to the given scenario.
+    #[cfg(test)]
+    pub(crate) fn for_scenario(&self, scenario: &str) -> Vec<CapturedError> {
+        self.lock()
+            .iter()
+            .filter(|e| e.scenario.as_deref() == Some(scenario))
+            .cloned()
+            .collect()
+    }
+
+    /// Removes and returns all errors captured so far.
+    pub(crate) fn take(&self) -> Vec<CapturedError> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// A human-readable summary, one line per error.
+    pub(crate) fn summary(&self) -> String {
+        let errors = self.lock();
+        if errors.is_empty() {
+            return "No wgpu errors captured".to_owned();
+        }
+
+        let mut summary = format!("{} wgpu error(s) captured:", errors.len());
+        for error in errors.iter() {
+            // Only the first line, the messages can be lengthy
+            let first_line = error.to_string();
+            let first_line = first_line.lines().next().unwrap_or_default();
+            summary.push_str("\n  ");
+            summary.push_str(first_line);
+        }
+        summary
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<CapturedError>> {
+        // A panic while holding the lock doesn't corrupt the log
+        self.errors.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl RenderContext {
+    /// Runs `f` within error scopes, see [`ErrorLog::capture`].
+    pub(crate) fn capture_errors<T>(
+        &self,
+        scenario: Option<&str>,
+        phase: Phase,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        self.errors.capture(&self.device, scenario, phase, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalSize;
+
+    #[test]
+    fn captures_scoped_errors() {
+        let Some(context) = RenderContext::test_context(PhysicalSize::new(16, 16)) else {
+            return;
+        };
+
+        // Mapping for both reading and writing is invalid
+        context.capture_errors(Some("broken"), Phase::PipelineCreation, || {
+            context.device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: 16,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::MAP_WRITE,
+                mapped_at_creation: false,
+            })
+        });
+
+        let errors = context.errors.for_scenario("broken");
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(errors[0].phase, Phase::PipelineCreation);
+        assert!(context
+            .errors
+            .summary()
+            .contains("`broken` during pipeline creation"));
+
+        assert_eq!(context.errors.take().len(), 1);
+        assert!(context.errors.is_empty());
+    }
+
+    #[test]
+    fn pops_scopes_on_panic() {
+        let Some(context) = RenderContext::test_context(PhysicalSize::new(16, 16)) else {
+            return;
+        };
+
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            context.capture_errors(Some("panicking"), Phase::PipelineCreation, || {
+                panic!("scenario failed")
+            })
+        }));
+        assert!(panicked.is_err());
+
+        // Errors outside of scopes reach the uncaptured error handler again
+        context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 16,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::MAP_WRITE,
+            mapped_at_creation: false,
+        });
+        context.device.poll(wgpu::Maintain::Wait);
+        let errors = context.errors.take();
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(errors[0].phase, Phase::Uncaptured);
+    }
+}
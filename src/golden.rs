@@ -0,0 +1,174 @@
+//! Golden-image regression tests of the scenarios.
+//!
+//! Each scenario is rendered offscreen at a fixed resolution and compared to
+//! the reference PNG in `tests/golden/`. On a mismatch, the actual frame and a
+//! diff image (mismatching pixels in red) are written to
+//! `target/golden-diff/`.
+//!
+//! Run with `UPDATE_GOLDEN=1` to (re)write the reference images.
+
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+use winit::dpi::PhysicalSize;
+
+use crate::capture;
+use crate::scenario::{self, Scenario};
+use crate::RenderContext;
+
+/// Resolution of the golden images.
+const SIZE: PhysicalSize<u32> = PhysicalSize::new(256, 256);
+/// Maximum difference per color channel.
+const TOLERANCE: u8 = 8;
+/// Number of pixels allowed to exceed the tolerance, e.g. at polygon edges.
+const MAX_MISMATCHED_PIXELS: usize = 16;
+
+/// Why an image doesn't match its reference.
+#[derive(Debug)]
+enum Mismatch {
+    /// The images have different dimensions, as `(width, height)`.
+    Size {
+        actual: (u32, u32),
+        expected: (u32, u32),
+    },
+    /// Too many pixels exceed the tolerance, marked in the diff image.
+    Pixels { count: usize, diff: RgbaImage },
+}
+
+/// Compares two images within the tolerances.
+fn compare(actual: &RgbaImage, expected: &RgbaImage) -> Result<(), Mismatch> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(Mismatch::Size {
+            actual: actual.dimensions(),
+            expected: expected.dimensions(),
+        });
+    }
+
+    let mut mismatches = 0;
+    let diff = RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let a = actual.get_pixel(x, y);
+        let e = expected.get_pixel(x, y);
+        let matches = a.0.iter().zip(e.0).all(|(a, e)| a.abs_diff(e) <= TOLERANCE);
+        if matches {
+            // Dimmed expected image for orientation
+            let luma = (e[0] as u32 + e[1] as u32 + e[2] as u32) / 3 / 4;
+            Rgba([luma as u8, luma as u8, luma as u8, 255])
+        } else {
+            mismatches += 1;
+            Rgba([255, 0, 0, 255])
+        }
+    });
+
+    if mismatches > MAX_MISMATCHED_PIXELS {
+        Err(Mismatch::Pixels {
+            count: mismatches,
+            diff,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+fn crate_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Renders the scenario and compares it to its reference image.
+fn check_scenario(name: &str) {
+    let Some(context) = RenderContext::test_context(SIZE) else {
+        return;
+    };
+
+    let entries = scenario::select(Some(&[name.to_owned()])).unwrap();
+    let scenarios: Vec<Box<dyn Scenario>> =
+        entries.iter().map(|entry| entry.build(&context)).collect();
+    let actual = context.capture_frame(&scenarios).unwrap();
+
+    let reference = crate_dir().join("tests/golden").join(format!("{name}.png"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(reference.parent().unwrap()).unwrap();
+        capture::save_png(&actual, &reference).unwrap();
+        return;
+    }
+
+    let expected = image::open(&reference)
+        .unwrap_or_else(|e| panic!("Failed to open {}: {e}", reference.display()))
+        .into_rgba8();
+
+    let Err(mismatch) = compare(&actual, &expected) else {
+        return;
+    };
+    let out_dir = crate_dir().join("target/golden-diff");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let actual_path = out_dir.join(format!("{name}.actual.png"));
+    capture::save_png(&actual, &actual_path).unwrap();
+
+    match mismatch {
+        Mismatch::Size {
+            actual: (width, height),
+            expected: (expected_width, expected_height),
+        } => panic!(
+            "`{name}` is {width}x{height}, but {} is {expected_width}x{expected_height}, see {}",
+            display(&reference),
+            display(&actual_path),
+        ),
+        Mismatch::Pixels { count, diff } => {
+            let diff_path = out_dir.join(format!("{name}.diff.png"));
+            capture::save_png(&diff, &diff_path).unwrap();
+            panic!(
+                "`{name}` differs from {} in {count} pixels, see {} and {}",
+                display(&reference),
+                display(&actual_path),
+                display(&diff_path),
+            );
+        }
+    }
+}
+
+fn display(path: &Path) -> String {
+    path.strip_prefix(crate_dir())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+#[test]
+fn only_pos() {
+    check_scenario("only_pos");
+}
+
+#[test]
+fn with_color() {
+    check_scenario("with_color");
+}
+
+#[test]
+fn compare_detects_differences() {
+    let expected = RgbaImage::from_pixel(8, 8, Rgba([10, 20, 30, 255]));
+
+    let mut actual = expected.clone();
+    actual.put_pixel(0, 0, Rgba([10 + TOLERANCE, 20, 30, 255]));
+    assert!(compare(&actual, &expected).is_ok());
+
+    for pixel in actual.pixels_mut() {
+        *pixel = Rgba([0, 20, 30, 255]);
+    }
+    let Err(Mismatch::Pixels { count, diff }) = compare(&actual, &expected) else {
+        panic!("expected mismatching pixels");
+    };
+    assert_eq!(count, 64);
+    assert_eq!(*diff.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+}
+
+#[test]
+fn compare_detects_size_mismatches() {
+    let expected = RgbaImage::new(8, 8);
+    let actual = RgbaImage::new(8, 4);
+    assert!(matches!(
+        compare(&actual, &expected),
+        Err(Mismatch::Size {
+            actual: (8, 4),
+            expected: (8, 8),
+        })
+    ));
+}
@@ -0,0 +1,177 @@
+//! Shader hot reloading on native.
+//!
+//! With `--hot-reload`, the scenarios load their shaders from the `src`
+//! directory of the crate instead of the embedded copies. The
+//! [`ShaderWatcher`] polls the loaded files for changes, and the scenarios
+//! using a changed file recreate their pipelines. If the new shader doesn't
+//! compile, the error is logged and the previous pipeline is kept.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use log::{error, info, warn};
+
+use crate::scenario::Scenario;
+use crate::RenderContext;
+
+/// How often to check the files for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The directory the shaders are loaded from.
+pub(crate) fn shader_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src")
+}
+
+/// The state of a file used to detect changes.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Polls shader files for changes.
+#[derive(Debug)]
+pub(crate) struct ShaderWatcher {
+    files: BTreeMap<PathBuf, Stamp>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    /// Watches the shader files of the given scenarios.
+    pub(crate) fn new(scenarios: &[Box<dyn Scenario>]) -> Self {
+        let mut watcher = Self {
+            files: BTreeMap::new(),
+            last_poll: Instant::now(),
+        };
+        watcher.watch(scenarios.iter().flat_map(|s| s.shader_files()));
+        info!("Watching {} shader file(s)", watcher.files.len());
+        watcher
+    }
+
+    /// Adds files to watch, e.g. newly included modules.
+    fn watch<'a>(&mut self, files: impl IntoIterator<Item = &'a PathBuf>) {
+        for file in files {
+            if !self.files.contains_key(file) {
+                self.files.insert(file.clone(), stamp(file));
+            }
+        }
+    }
+
+    /// Returns the files changed since the last call.
+    pub(crate) fn changed(&mut self) -> Vec<PathBuf> {
+        self.last_poll = Instant::now();
+        self.files
+            .iter_mut()
+            .filter_map(|(path, last)| {
+                let current = stamp(path);
+                (current != *last).then(|| {
+                    *last = current;
+                    path.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// Recreates the pipelines of the scenarios using a changed file.
+    ///
+    /// Does nothing if the files were checked recently.
+    pub(crate) fn reload_changed(
+        &mut self,
+        context: &RenderContext,
+        scenarios: &mut [Box<dyn Scenario>],
+    ) {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return;
+        }
+        let changed = self.changed();
+        if changed.is_empty() {
+            return;
+        }
+
+        for scenario in scenarios.iter_mut() {
+            if !scenario.shader_files().iter().any(|f| changed.contains(f)) {
+                continue;
+            }
+            match scenario.reload_shaders(context) {
+                Ok(()) => warn!("Reloaded the shaders of `{}`", scenario.name()),
+                Err(msg) => error!(
+                    "Failed to reload the shaders of `{}`, keeping the previous pipeline: {msg}",
+                    scenario.name()
+                ),
+            }
+        }
+
+        // The shaders may include other modules now
+        self.watch(scenarios.iter().flat_map(|s| s.shader_files()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario;
+    use winit::dpi::PhysicalSize;
+
+    /// Copies the shaders of `only_pos` to a new temporary shader directory.
+    fn copy_shaders(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        for file in [
+            "only_pos/shader.wgsl",
+            "shaders/color.wgsl",
+            "shaders/instance.wgsl",
+            "shaders/view.wgsl",
+        ] {
+            let target = dir.join(file);
+            std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+            std::fs::copy(shader_dir().join(file), target).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn detects_changes() {
+        let dir = copy_shaders("detects_changes");
+        let file = dir.join("shaders/view.wgsl");
+
+        let mut watcher = ShaderWatcher {
+            files: BTreeMap::new(),
+            last_poll: Instant::now(),
+        };
+        watcher.watch([&file]);
+        assert_eq!(watcher.changed(), Vec::<PathBuf>::new());
+
+        std::fs::write(&file, "// changed").unwrap();
+        assert_eq!(watcher.changed(), [file]);
+        assert_eq!(watcher.changed(), Vec::<PathBuf>::new());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_pipeline_on_error() {
+        let Some(mut context) = RenderContext::test_context(PhysicalSize::new(16, 16)) else {
+            return;
+        };
+        let dir = copy_shaders("keeps_pipeline_on_error");
+        context.shader_dir = Some(dir.clone());
+
+        let entry = &scenario::select(Some(&["only_pos".to_owned()])).unwrap()[0];
+        let mut only_pos = entry.build(&context);
+        assert!(only_pos
+            .shader_files()
+            .contains(&dir.join("shaders/color.wgsl")));
+
+        let shader = dir.join("only_pos/shader.wgsl");
+        let source = std::fs::read_to_string(&shader).unwrap();
+        std::fs::write(&shader, format!("{source}\nfn broken(")).unwrap();
+        assert!(only_pos.reload_shaders(&context).is_err());
+
+        std::fs::write(&shader, source).unwrap();
+        only_pos.reload_shaders(&context).unwrap();
+        assert!(context.errors.is_empty(), "{}", context.errors.summary());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
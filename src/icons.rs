@@ -0,0 +1,91 @@
+//! Scenarios drawing SVG icons of the schedule diagrams, tessellated by
+//! [`SvgPath`].
+//!
+//! The paths are authored in a 24x24 view box like the icon files, and drawn
+//! through the same instanced pipeline as the hexagons.
+
+use wgpu::PrimitiveTopology;
+
+use crate::mesh::{FillRule, Geometry, SvgPath};
+use crate::polygon::{row_entries, RowCase};
+use crate::scenario::ScenarioEntry;
+use crate::with_color::{self, PolygonVertex};
+
+/// The view box of the icon paths.
+const VIEW_BOX: [f32; 4] = [0.0, 0.0, 24.0, 24.0];
+
+/// An SVG icon.
+struct Icon {
+    /// The `d` attribute of the path.
+    data: &'static str,
+    fill: Option<(FillRule, [f32; 3])>,
+    /// The stroke width, relative to the icon size, and color.
+    stroke: Option<(f32, [f32; 3])>,
+}
+
+const CASES: &[RowCase<Icon>] = &[
+    RowCase {
+        name: "icon_station",
+        description: "Station icon, a circle of two arcs filled and stroked.",
+        spec: Icon {
+            data: "M12 3 A9 9 0 0 1 12 21 A9 9 0 0 1 12 3 Z",
+            fill: Some((FillRule::NonZero, [0.95, 0.95, 0.95])),
+            stroke: Some((0.08, [0.1, 0.3, 0.8])),
+        },
+    },
+    RowCase {
+        name: "icon_signal",
+        description: "Signal icon, a rounded rectangle with three round holes (even-odd fill).",
+        spec: Icon {
+            data: "M8 2 H16 Q18 2 18 4 V20 Q18 22 16 22 H8 Q6 22 6 20 V4 Q6 2 8 2 Z \
+                   M9.8 6.5 a2.2 2.2 0 1 0 4.4 0 a2.2 2.2 0 1 0 -4.4 0 Z \
+                   M9.8 12 a2.2 2.2 0 1 0 4.4 0 a2.2 2.2 0 1 0 -4.4 0 Z \
+                   M9.8 17.5 a2.2 2.2 0 1 0 4.4 0 a2.2 2.2 0 1 0 -4.4 0 Z",
+            fill: Some((FillRule::EvenOdd, [0.55, 0.55, 0.55])),
+            stroke: None,
+        },
+    },
+    RowCase {
+        name: "icon_track",
+        description: "Track icon, an open path of cubic and quadratic curves, only stroked.",
+        spec: Icon {
+            data: "M2 20 C8 20 6 12 12 12 S16 4 22 4 M2 14 q4 -6 8 -2 t10 -8",
+            fill: None,
+            stroke: Some((0.06, [1.0, 0.6, 0.1])),
+        },
+    },
+    RowCase {
+        name: "icon_arrow",
+        description: "Direction arrow icon of lines, filled and stroked.",
+        spec: Icon {
+            data: "M3 9 H13 V4 L21 12 L13 20 V15 H3 Z",
+            fill: Some((FillRule::NonZero, [0.2, 0.7, 0.3])),
+            stroke: Some((0.04, [1.0, 1.0, 1.0])),
+        },
+    },
+];
+
+/// Tessellates the fill and stroke of an icon.
+fn geometry(case: &RowCase<Icon>) -> Geometry<PolygonVertex> {
+    let icon = &case.spec;
+    let path = SvgPath::parse(icon.data)
+        .unwrap_or_else(|e| panic!("Invalid path of `{}`: {e}", case.name))
+        .in_view_box(VIEW_BOX);
+    let mut geometry = Geometry {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        topology: PrimitiveTopology::TriangleList,
+    };
+    if let Some((rule, color)) = icon.fill {
+        geometry.append(path.fill(rule, color));
+    }
+    if let Some((width, color)) = icon.stroke {
+        geometry.append(path.stroke(width, color));
+    }
+    geometry
+}
+
+/// Returns the registry entries of all icons.
+pub(crate) fn entries() -> Vec<ScenarioEntry> {
+    row_entries(CASES, with_color::SHADER, 0.3, 0.2, geometry)
+}
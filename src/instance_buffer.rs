@@ -0,0 +1,183 @@
+//! Growable GPU buffers of per-instance data.
+//!
+//! An [`InstanceBuffer`] keeps a CPU copy of the instances and records which
+//! of them changed. [`InstanceBuffer::upload`] then writes only the changed
+//! ranges with `queue.write_buffer`, or reallocates the buffer with twice the
+//! capacity if the instances don't fit anymore.
+
+use std::ops::Range;
+
+use crate::RenderContext;
+
+/// Instances in a vertex buffer, updated from the CPU.
+pub(crate) struct InstanceBuffer<T> {
+    label: &'static str,
+    buffer: wgpu::Buffer,
+    /// The number of instances the buffer has room for.
+    capacity: usize,
+    instances: Vec<T>,
+    /// The ranges of instances changed since the last upload, unordered and
+    /// possibly overlapping.
+    changed: Vec<Range<usize>>,
+}
+
+impl<T: bytemuck::Pod> InstanceBuffer<T> {
+    pub(crate) fn new(context: &RenderContext, label: &'static str, instances: Vec<T>) -> Self {
+        assert!(
+            std::mem::size_of::<T>() % wgpu::COPY_BUFFER_ALIGNMENT as usize == 0,
+            "instances must be a multiple of {} bytes to be written",
+            wgpu::COPY_BUFFER_ALIGNMENT
+        );
+        let capacity = instances.len().max(1);
+        let mut buffer = Self {
+            label,
+            buffer: Self::create_buffer(context, label, capacity),
+            capacity,
+            changed: Vec::new(),
+            instances,
+        };
+        buffer.mark_all_changed();
+        buffer.upload(context);
+        buffer
+    }
+
+    fn create_buffer(context: &RenderContext, label: &str, capacity: usize) -> wgpu::Buffer {
+        context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity * std::mem::size_of::<T>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// The CPU copy of the instances, possibly not uploaded yet.
+    pub(crate) fn instances(&self) -> &[T] {
+        &self.instances
+    }
+
+    /// Appends an instance and returns its index.
+    pub(crate) fn push(&mut self, instance: T) -> usize {
+        let index = self.instances.len();
+        self.instances.push(instance);
+        self.changed.push(index..index + 1);
+        index
+    }
+
+    /// Removes an instance, replacing it by the last one to keep the change
+    /// to a single instance.
+    pub(crate) fn remove(&mut self, index: usize) -> T {
+        let instance = self.instances.swap_remove(index);
+        if index < self.instances.len() {
+            self.changed.push(index..index + 1);
+        }
+        instance
+    }
+
+    /// Replaces the instance at `index`.
+    pub(crate) fn update(&mut self, index: usize, instance: T) {
+        self.instances[index] = instance;
+        self.changed.push(index..index + 1);
+    }
+
+    fn mark_all_changed(&mut self) {
+        self.changed.clear();
+        self.changed.push(0..self.instances.len());
+    }
+
+    /// Writes the changes to the buffer, reallocating it if needed.
+    pub(crate) fn upload(&mut self, context: &RenderContext) {
+        if self.instances.len() > self.capacity {
+            while self.capacity < self.instances.len() {
+                self.capacity *= 2;
+            }
+            log::debug!("Growing `{}` to {} instances", self.label, self.capacity);
+            self.buffer = Self::create_buffer(context, self.label, self.capacity);
+            self.mark_all_changed();
+        }
+
+        let len = self.instances.len();
+        for range in merge_ranges(std::mem::take(&mut self.changed)) {
+            // Removed instances needn't be written
+            let range = range.start..range.end.min(len);
+            if range.is_empty() {
+                continue;
+            }
+            let offset = range.start * std::mem::size_of::<T>();
+            context.queue.write_buffer(
+                &self.buffer,
+                offset as wgpu::BufferAddress,
+                bytemuck::cast_slice(&self.instances[range]),
+            );
+        }
+    }
+
+    /// The uploaded instances, for `set_vertex_buffer`.
+    ///
+    /// Panics if there are no instances, as slices can't be empty.
+    pub(crate) fn slice(&self) -> wgpu::BufferSlice<'_> {
+        let size = self.instances.len() * std::mem::size_of::<T>();
+        self.buffer.slice(..size as wgpu::BufferAddress)
+    }
+}
+
+/// Sorts the ranges and merges the overlapping and adjacent ones.
+fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalSize;
+
+    #[test]
+    fn merges_changed_ranges() {
+        assert_eq!(
+            merge_ranges(vec![5..6, 0..2, 2..3, 7..9, 8..10, 1..2]),
+            [0..3, 5..6, 7..10]
+        );
+        assert!(merge_ranges(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn grows_by_doubling() {
+        let Some(context) = RenderContext::test_context(PhysicalSize::new(16, 16)) else {
+            return;
+        };
+
+        let mut buffer = InstanceBuffer::new(&context, "Test Instances", vec![[0u32; 4]; 3]);
+        assert_eq!(buffer.capacity, 3);
+        for i in 0..4 {
+            buffer.push([i; 4]);
+        }
+        buffer.upload(&context);
+        assert_eq!((buffer.len(), buffer.capacity), (7, 12));
+
+        buffer.update(1, [9; 4]);
+        assert_eq!(buffer.remove(0), [0; 4]);
+        assert_eq!(buffer.instances()[..2], [[3; 4], [9; 4]]);
+        buffer.remove(buffer.len() - 1);
+        buffer.upload(&context);
+        assert_eq!(buffer.capacity, 12);
+        assert_eq!(buffer.len(), 5);
+
+        context.device.poll(wgpu::Maintain::Wait);
+        assert!(context.errors.is_empty(), "{}", context.errors.summary());
+    }
+}
@@ -1,6 +1,6 @@
-use either::Either;
-use only_pos::OnlyPos;
-use wgpu::TextureFormat;
+use config::RenderConfig;
+use options::Options;
+use scenario::{Scenario, ScenarioEntry};
 use winit::{event::Event, event_loop::ControlFlow};
 
 use log::info;
@@ -10,134 +10,152 @@ use winit::{
     event_loop::EventLoop,
     window::Window,
 };
-use with_color::WithColor;
 
+mod capture;
+mod color;
+mod compact;
+mod config;
+mod context;
+mod errors;
+#[cfg(test)]
+mod golden;
+#[cfg(not(target_arch = "wasm32"))]
+mod hot_reload;
+mod icons;
+mod instance_buffer;
+mod json;
+mod markers;
+mod mesh;
+#[cfg(not(target_arch = "wasm32"))]
+mod model;
 mod only_pos;
 //mod only_pos_instanced;
+mod options;
+mod permutation;
+mod polygon;
+mod reflect;
+mod report;
+mod scenario;
+mod shader;
+mod shapes;
+mod surface;
+#[cfg(not(target_arch = "wasm32"))]
+mod sweep;
+mod topology;
+mod webgl2;
 mod with_color;
 //mod with_color_instanced;
 
-struct RenderContext {
-    config: wgpu::SurfaceConfiguration,
-    surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    swapchain_format: wgpu::TextureFormat,
-}
-impl RenderContext {
-    async fn new(window: &Window) -> Self {
-        let size = window.inner_size();
-
-        let instance = wgpu::Instance::default();
-        info!("{instance:?}");
-        let surface_result = unsafe { instance.create_surface(window) };
-        let surface = surface_result.expect("Failed to create surface");
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                // Request an adapter which can render to our surface
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("Failed to find an appropriate adapter");
-        info!("{adapter:?}");
-
-        // Create the logical device and command queue
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::empty(),
-                    // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
-                    limits: wgpu::Limits::downlevel_webgl2_defaults()
-                        .using_resolution(adapter.limits()),
-                },
-                None,
-            )
+pub(crate) use context::RenderContext;
+
+/// Size of the offscreen target in headless mode.
+#[cfg(not(target_arch = "wasm32"))]
+const HEADLESS_SIZE: winit::dpi::PhysicalSize<u32> = winit::dpi::PhysicalSize::new(800, 600);
+
+/// Renders a single frame without a window.
+#[cfg(not(target_arch = "wasm32"))]
+async fn run_headless(options: Options, config: RenderConfig, scenarios: Vec<ScenarioEntry>) {
+    let mut context =
+        RenderContext::new_headless(&config, wgpu::TextureFormat::Rgba8Unorm, HEADLESS_SIZE)
             .await
-            .expect("Failed to create device");
-        info!("{device:?}");
-
-        let mut caps = surface.get_capabilities(&adapter);
-
-        let format_priority = |format: &TextureFormat| {
-            if matches!(
-                format.describe().sample_type,
-                wgpu::TextureSampleType::Float { .. }
-            ) && format
-                .describe()
-                .guaranteed_format_features
-                .allowed_usages
-                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
-            {
-                format.describe().components as u32 * 2 + format.describe().srgb as u32
-            } else {
-                0
-            }
-        };
-        caps.formats.sort_by_key(format_priority);
-        caps.formats.reverse();
-
-        let present_mode_priority = |present_mode: &wgpu::PresentMode| {
-            match present_mode {
-                // Fifo guarantees no tearing and keeps the framerate at the monitor refresh rate.
-                wgpu::PresentMode::Fifo => 10,
-                // Should not tear and keeps the framerate at the monitor refresh rate.
-                // Tears if the framerate is too low.
-                wgpu::PresentMode::FifoRelaxed => 9,
-                // Same as above.
-                wgpu::PresentMode::AutoVsync => 8,
-                // No tearing, but framerate can be higher than the monitor refresh rate.
-                wgpu::PresentMode::Mailbox => 7,
-                // No vsync at all.
-                wgpu::PresentMode::Immediate => 6,
-                // Goes through most modes above
-                wgpu::PresentMode::AutoNoVsync => 5,
-            }
-        };
-        caps.present_modes.sort_by_key(present_mode_priority);
-        caps.present_modes.reverse();
-
-        info!("{caps:?}");
-
-        let swapchain_format = *caps
-            .formats
-            .first()
-            .expect("No supported swap-chain texture formats");
-        let present_mode = *caps
-            .present_modes
-            .first()
-            .expect("No supported present modes");
-
-        let sample_flags = swapchain_format.describe().guaranteed_format_features.flags;
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: swapchain_format,
-            width: size.width,
-            height: size.height,
-            present_mode,
-            alpha_mode: caps.alpha_modes[0],
-            view_formats: vec![swapchain_format],
-        };
-
-        surface.configure(&device, &config);
-
-        Self {
-            config,
-            surface,
-            device,
-            queue,
-            swapchain_format,
+            .unwrap_or_else(|| exit_with_error(&format!("No adapter matching {config:?}")));
+    context.compact_locations = options.compact_locations;
+
+    let mut scenarios = build_scenarios(&context, &scenarios);
+    for scenario in &mut scenarios {
+        scenario.update(&context);
+    }
+    let frame = context
+        .capture_frame(&scenarios)
+        .unwrap_or_else(|msg| exit_with_error(&msg));
+    info!("Rendered a {}x{} frame", frame.width(), frame.height());
+
+    if let Some(output) = &options.output {
+        capture::save_png(&frame, output.as_ref()).unwrap_or_else(|msg| exit_with_error(&msg));
+    }
+
+    finish(&context, &scenarios, &options);
+}
+
+/// Prints the summary of the captured wgpu errors and writes the report, if
+/// requested.
+fn finish(context: &RenderContext, scenarios: &[Box<dyn Scenario>], options: &Options) {
+    if context.errors.is_empty() {
+        info!("{}", context.errors.summary());
+    } else {
+        warn!("{}", context.errors.summary());
+    }
+
+    if let Some(path) = &options.report {
+        if let Err(msg) = report::write_report(context, scenarios, path) {
+            log::error!("{msg}");
         }
     }
 }
 
-async fn run(event_loop: EventLoop<()>, window: Window) {
-    let mut context = RenderContext::new(&window).await;
-    let only_pos = OnlyPos::new(&context);
-    let with_color = WithColor::new(&context);
+/// Saves the next frame as `screenshot-<unix time>.png`.
+fn save_screenshot(context: &RenderContext, scenarios: &[Box<dyn Scenario>]) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = format!("screenshot-{timestamp}.png");
+
+        let saved = context
+            .capture_frame(scenarios)
+            .and_then(|frame| capture::save_png(&frame, path.as_ref()));
+        match saved {
+            Ok(()) => warn!("Saved screenshot to {path}"),
+            Err(msg) => log::error!("{msg}"),
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = (context, scenarios);
+        warn!("Screenshots are not supported on the web");
+    }
+}
+
+fn build_scenarios(context: &RenderContext, entries: &[ScenarioEntry]) -> Vec<Box<dyn Scenario>> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            if let Some(reason) = entry.unsupported_reason(context) {
+                warn!("Skipping scenario {}: {reason}", entry.name);
+                return None;
+            }
+            info!("Scenario {}: {}", entry.name, entry.description);
+            Some(entry.build(context))
+        })
+        .collect()
+}
+
+async fn run(
+    event_loop: EventLoop<()>,
+    window: Window,
+    options: Options,
+    config: RenderConfig,
+    scenarios: Vec<ScenarioEntry>,
+) {
+    let mut context = RenderContext::new(&window, &config).await;
+    context.compact_locations = options.compact_locations;
+    #[cfg(not(target_arch = "wasm32"))]
+    if options.hot_reload {
+        context.shader_dir = Some(hot_reload::shader_dir());
+    }
+    #[cfg(target_arch = "wasm32")]
+    if options.hot_reload {
+        warn!("Hot reloading is not supported on the web");
+    }
+
+    #[allow(unused_mut)]
+    let mut scenarios = build_scenarios(&context, &scenarios);
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut watcher = options
+        .hot_reload
+        .then(|| hot_reload::ShaderWatcher::new(&scenarios));
 
     event_loop.run(move |event, _, control_flow| {
         // Have the closure take ownership of the resources.
@@ -153,88 +171,40 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         // Handle window events.
         match event {
             Event::RedrawRequested(_) => {
-                // Get next frame
-                let frame = context
-                    .surface
-                    .get_current_texture()
-                    .expect("Failed to acquire next swap chain texture");
-
-                // Get frame texture view
-                let view = frame
-                    .texture
-                    .create_view(&wgpu::TextureViewDescriptor::default());
-
-                let secondary_pass = {
-                    let mut secondary_pass = context.device.create_render_bundle_encoder(
-                        &wgpu::RenderBundleEncoderDescriptor {
-                            label: None,
-                            color_formats: &[Some(context.config.format)],
-                            depth_stencil: None,
-                            sample_count: 1,
-                            multiview: None,
-                        },
-                    );
-
-                    // Render the objects
-                    only_pos.render(&mut secondary_pass, &context);
-                    with_color.render(&mut secondary_pass, &context);
-
-                    secondary_pass.finish(&Default::default())
-                };
-
-                // Create a command encoder (to record draw calls)
-                let mut encoder = context
-                    .device
-                    .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-                {
-                    // Create a render pass
-                    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: None,
-                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                                store: true,
+                context.render_frame(&scenarios);
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(VirtualKeyCode::F11),
+                                state: ElementState::Pressed,
+                                ..
                             },
-                        })],
-                        depth_stencil_attachment: None,
-                    });
-
-                    pass.execute_bundles([&secondary_pass]);
-                }
-
-                // Submit command buffer and present frame
-                context.queue.submit(Some(encoder.finish()));
-                frame.present();
+                        ..
+                    },
+                ..
+            } => {
+                save_screenshot(&context, &scenarios);
             }
             Event::WindowEvent {
                 event:
                     WindowEvent::KeyboardInput {
                         input:
                             KeyboardInput {
-                                virtual_keycode,
-                                state,
-                                scancode,
+                                virtual_keycode: Some(VirtualKeyCode::F12 | VirtualKeyCode::Escape),
+                                state: ElementState::Pressed,
                                 ..
                             },
                         ..
                     },
                 ..
             } => {
-                let is_pressed = state == ElementState::Pressed;
-
-                if let Some(vk) = virtual_keycode {
-                    match vk {
-                        VirtualKeyCode::F12 | VirtualKeyCode::Escape => {
-                            warn!("F12 pressed, quit!");
-                            std::process::exit(0);
-                        }
-                        _ => (),
-                    }
-                }
+                warn!("F12 pressed, quit!");
+                finish(&context, &scenarios, &options);
+                std::process::exit(0);
             }
-            /*
             Event::WindowEvent {
                 event: WindowEvent::Resized(size),
                 ..
@@ -255,15 +225,35 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 // On macos the window needs to be redrawn manually after resizing
                 window.request_redraw();
             }
-            */
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
             } => {
                 *control_flow = ControlFlow::Exit;
             }
+            Event::LoopDestroyed => {
+                finish(&context, &scenarios, &options);
+            }
             // Handle the main events cleared event
             Event::MainEventsCleared => {
+                // Follow the size of the browser window, winit then emits
+                // `Resized`
+                #[cfg(target_arch = "wasm32")]
+                if let Some(size) = browser_window_size() {
+                    if window.inner_size() != size.to_physical(window.scale_factor()) {
+                        window.set_inner_size(size);
+                    }
+                }
+
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(watcher) = &mut watcher {
+                    watcher.reload_changed(&context, &mut scenarios);
+                }
+
+                for scenario in &mut scenarios {
+                    scenario.update(&context);
+                }
+
                 // Manually request Redraw
                 window.request_redraw();
             }
@@ -272,23 +262,39 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     });
 }
 
-fn main() {
-    let event_loop = EventLoop::new();
-    let builder = winit::window::WindowBuilder::new().with_title("Railroad Scheduler");
-
-    let window = builder.build(&event_loop).unwrap();
+/// The inner size of the browser window.
+#[cfg(target_arch = "wasm32")]
+fn browser_window_size() -> Option<winit::dpi::LogicalSize<f64>> {
+    let win = web_sys::window()?;
+    let width = win.inner_width().ok()?.as_f64()?;
+    let height = win.inner_height().ok()?.as_f64()?;
+    Some(winit::dpi::LogicalSize::new(width, height))
+}
 
+/// Reports an invalid configuration and terminates.
+fn exit_with_error(msg: &str) -> ! {
     #[cfg(not(target_arch = "wasm32"))]
     {
-        env_logger::init();
-        // Temporarily avoid srgb formats for the swapchain on the web
-        pollster::block_on(run(event_loop, window));
+        eprintln!("Error: {msg}");
+        std::process::exit(2);
     }
     #[cfg(target_arch = "wasm32")]
     {
+        // The panic hook reports this in the browser console
+        panic!("{msg}");
+    }
+}
+
+fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    let options = {
+        env_logger::init();
+
+        Options::from_env().unwrap_or_else(|msg| exit_with_error(&msg))
+    };
+    #[cfg(target_arch = "wasm32")]
+    let options = {
         use log::Level;
-        use winit::platform::web::WindowBuilderExtWebSys;
-        use winit::platform::web::WindowExtWebSys;
 
         // Set the panic hook to print to the console.
         std::panic::set_hook(Box::new(console_error_panic_hook::hook));
@@ -296,6 +302,85 @@ fn main() {
         // Setup the logger to print to the console as well.
         console_log::init_with_level(Level::Warn).expect("could not initialize logger");
 
+        Options::from_env().unwrap_or_else(|msg| exit_with_error(&msg))
+    };
+
+    if options.list_scenarios {
+        for entry in scenario::registry() {
+            let default = if entry.in_default { " (default)" } else { "" };
+            #[cfg(not(target_arch = "wasm32"))]
+            println!("{}{default}: {}", entry.name, entry.description);
+            #[cfg(target_arch = "wasm32")]
+            warn!("{}{default}: {}", entry.name, entry.description);
+        }
+        return;
+    }
+
+    // A mesh is drawn alone unless scenarios are selected explicitly, and the
+    // sweep runs every registered scenario by default
+    #[allow(unused_mut)]
+    let mut scenarios = if options.scenarios.is_some() {
+        scenario::select(options.scenarios.as_deref()).unwrap_or_else(|msg| exit_with_error(&msg))
+    } else if options.mesh.is_some() {
+        Vec::new()
+    } else if options.sweep {
+        scenario::registry()
+    } else {
+        scenario::select(None).unwrap_or_else(|msg| exit_with_error(&msg))
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = &options.mesh {
+        scenarios.push(model::entry(path.as_ref()).unwrap_or_else(|msg| exit_with_error(&msg)));
+    }
+    #[cfg(target_arch = "wasm32")]
+    if options.mesh.is_some() {
+        warn!("Loading meshes is not supported on the web");
+    }
+    let config = RenderConfig::from_options(&options).unwrap_or_else(|msg| exit_with_error(&msg));
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if options.sweep {
+        let results = sweep::sweep(&options, &config, &scenarios);
+        if results.is_empty() {
+            exit_with_error(&format!("No adapter matching {config:?}"));
+        }
+        println!("{}", sweep::format_table(&results));
+
+        let failures = results
+            .iter()
+            .filter(|r| {
+                matches!(
+                    r.outcome,
+                    sweep::Outcome::ValidationError(_) | sweep::Outcome::Fail(_)
+                )
+            })
+            .count();
+        if failures > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if options.headless {
+        pollster::block_on(run_headless(options, config, scenarios));
+        return;
+    }
+
+    let event_loop = EventLoop::new();
+    let builder = winit::window::WindowBuilder::new().with_title("Railroad Scheduler");
+
+    let window = builder.build(&event_loop).unwrap();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        pollster::block_on(run(event_loop, window, options, config, scenarios));
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::WindowBuilderExtWebSys;
+        use winit::platform::web::WindowExtWebSys;
+
         // Append the canvas to the document body
         {
             let win = web_sys::window().expect("no global `window` exists");
@@ -315,6 +400,6 @@ fn main() {
         }
 
         // Spawn the main loop.
-        wasm_bindgen_futures::spawn_local(run(event_loop, window));
+        wasm_bindgen_futures::spawn_local(run(event_loop, window, options, config, scenarios));
     }
 }
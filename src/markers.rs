@@ -0,0 +1,131 @@
+//! Train markers moving along horizontal tracks every frame.
+//!
+//! Exercises the dynamic instances of [`PolygonRenderer`]: every frame each
+//! marker moves, markers leaving the tracks are removed and new ones enter
+//! at regular intervals, so the [`InstanceBuffer`] sees updates, removals
+//! and growth.
+//!
+//! [`InstanceBuffer`]: crate::instance_buffer::InstanceBuffer
+
+use std::path::PathBuf;
+
+use crate::mesh::rect;
+use crate::polygon::{PolygonDesc, PolygonInstance, PolygonRenderer};
+use crate::scenario::{self, Scenario};
+use crate::with_color::{self, PolygonVertex};
+use crate::RenderContext;
+
+/// Number of tracks, each with its own speed.
+const TRACKS: usize = 8;
+/// Horizontal extent of the tracks.
+const LEFT: f32 = -0.95;
+const RIGHT: f32 = 0.95;
+/// Frames between two markers entering a track.
+const SPAWN_INTERVAL: u64 = 20;
+/// Scale of the unit sized marker shape.
+const MARKER_SCALE: f32 = 0.03;
+
+fn track_y(track: usize) -> f32 {
+    -0.3 - track as f32 * 0.05
+}
+
+fn track_speed(track: usize) -> f32 {
+    0.004 + track as f32 * 0.001
+}
+
+pub(crate) struct TrainMarkers {
+    renderer: PolygonRenderer<PolygonVertex, PolygonInstance>,
+    /// The speed of each instance, in the same order.
+    speeds: Vec<f32>,
+    frame: u64,
+}
+
+impl TrainMarkers {
+    pub(crate) const NAME: &'static str = "train_markers";
+    pub(crate) const DESCRIPTION: &'static str =
+        "Train markers moving every frame, with instances updated, added and removed.";
+
+    pub(crate) fn new(context: &RenderContext) -> Self {
+        // Start with the tracks already populated
+        let (instances, speeds) = (0..TRACKS)
+            .flat_map(|track| {
+                let speed = track_speed(track);
+                let spacing = speed * SPAWN_INTERVAL as f32;
+                let count = ((RIGHT - LEFT) / spacing) as usize;
+                (0..count).map(move |i| {
+                    let position = [LEFT + i as f32 * spacing, track_y(track)];
+                    (PolygonInstance::new(position, MARKER_SCALE), speed)
+                })
+            })
+            .unzip();
+
+        Self {
+            renderer: PolygonRenderer::from_desc(
+                context,
+                PolygonDesc {
+                    name: Self::NAME,
+                    description: Self::DESCRIPTION,
+                    shader: with_color::SHADER,
+                    geometry: rect(1.0, 0.5)
+                        .with_color([1.0, 0.4, 0.2])
+                        .geometry(wgpu::PrimitiveTopology::TriangleList),
+                    instances,
+                },
+            ),
+            speeds,
+            frame: 0,
+        }
+    }
+}
+
+impl Scenario for TrainMarkers {
+    fn name(&self) -> &str {
+        self.renderer.name()
+    }
+
+    fn description(&self) -> &str {
+        self.renderer.description()
+    }
+
+    fn vertex_layouts(&self) -> &[scenario::VertexLayout] {
+        self.renderer.vertex_layouts()
+    }
+
+    fn shader_files(&self) -> &[PathBuf] {
+        self.renderer.shader_files()
+    }
+
+    fn reload_shaders(&mut self, context: &RenderContext) -> Result<(), String> {
+        self.renderer.reload_shaders(context)
+    }
+
+    fn update(&mut self, context: &RenderContext) {
+        self.frame += 1;
+        let instances = self.renderer.instances_mut();
+
+        // Backwards, as removing moves the last instance into the gap
+        for i in (0..instances.len()).rev() {
+            let mut instance = instances.instances()[i];
+            instance.transform[3][0] += self.speeds[i];
+            if instance.transform[3][0] > RIGHT {
+                instances.remove(i);
+                self.speeds.swap_remove(i);
+            } else {
+                instances.update(i, instance);
+            }
+        }
+
+        if self.frame % SPAWN_INTERVAL == 0 {
+            for track in 0..TRACKS {
+                instances.push(PolygonInstance::new([LEFT, track_y(track)], MARKER_SCALE));
+                self.speeds.push(track_speed(track));
+            }
+        }
+
+        self.renderer.update(context);
+    }
+
+    fn render<'a>(&'a self, pass: &mut wgpu::RenderBundleEncoder<'a>) {
+        self.renderer.render(pass);
+    }
+}
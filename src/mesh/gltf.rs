@@ -0,0 +1,472 @@
+//! glTF 2.0 parsing, of both `.gltf` and binary `.glb` files.
+//!
+//! Reads the `POSITION`, `NORMAL`, `TEXCOORD_0` and `COLOR_0` attributes and
+//! the indices of all primitives of all meshes. Buffers are embedded as
+//! base64 `data:` URIs, stored in external files or in the binary chunk of a
+//! `.glb`. Node transforms, materials and sparse accessors aren't supported,
+//! so meshes are drawn in their own coordinates.
+
+use std::path::Path;
+
+use wgpu::PrimitiveTopology;
+
+use super::Mesh;
+use crate::color::linear_to_srgb;
+use crate::json::Json;
+
+/// `glTF` in little endian, the magic of `.glb` files.
+const GLB_MAGIC: u32 = 0x4654_6C67;
+/// The type of the JSON chunk of a `.glb`.
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+/// The type of the binary chunk of a `.glb`.
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+/// Parses a `.gltf` file, with external buffers relative to `base`.
+pub(super) fn parse_gltf(source: &str, base: &Path) -> Result<Mesh, String> {
+    let document = Json::parse(source).map_err(|e| format!("invalid JSON: {e}"))?;
+    Document::load(&document, None, base)?.mesh()
+}
+
+/// Parses a `.glb` file, with external buffers relative to `base`.
+pub(super) fn parse_glb(bytes: &[u8], base: &Path) -> Result<Mesh, String> {
+    let word = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .ok_or_else(|| "truncated GLB".to_owned())
+    };
+    if word(0)? != GLB_MAGIC {
+        return Err("not a GLB file".to_owned());
+    }
+    if word(4)? != 2 {
+        return Err(format!("unsupported glTF version {}", word(4)?));
+    }
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while offset < bytes.len() {
+        let length = word(offset)? as usize;
+        let kind = word(offset + 4)?;
+        let data = bytes
+            .get(offset + 8..offset + 8 + length)
+            .ok_or("truncated GLB chunk")?;
+        match kind {
+            CHUNK_JSON => json = Some(data),
+            CHUNK_BIN => bin = Some(data.to_vec()),
+            // Unknown chunks must be ignored
+            _ => {}
+        }
+        offset += 8 + length;
+    }
+
+    let json = json.ok_or("missing JSON chunk")?;
+    let json = std::str::from_utf8(json).map_err(|e| format!("invalid JSON chunk: {e}"))?;
+    let document = Json::parse(json).map_err(|e| format!("invalid JSON: {e}"))?;
+    Document::load(&document, bin, base)?.mesh()
+}
+
+/// A glTF document with its buffers loaded.
+struct Document<'a> {
+    json: &'a Json,
+    buffers: Vec<Vec<u8>>,
+}
+
+/// Looks up a required member.
+fn member<'a>(json: &'a Json, key: &str) -> Result<&'a Json, String> {
+    json.get(key).ok_or_else(|| format!("missing `{key}`"))
+}
+
+/// Looks up a required index or size.
+fn usize_member(json: &Json, key: &str) -> Result<usize, String> {
+    member(json, key)?
+        .as_usize()
+        .ok_or_else(|| format!("`{key}` is not an unsigned integer"))
+}
+
+/// Looks up the `index`th element of the top level array `key`.
+fn element<'a>(json: &'a Json, key: &str, index: usize) -> Result<&'a Json, String> {
+    json.get(key)
+        .and_then(Json::as_array)
+        .and_then(|elements| elements.get(index))
+        .ok_or_else(|| format!("missing `{key}[{index}]`"))
+}
+
+impl<'a> Document<'a> {
+    /// Loads the buffers, the first of which may be the binary chunk of a
+    /// `.glb`.
+    fn load(json: &'a Json, bin: Option<Vec<u8>>, base: &Path) -> Result<Self, String> {
+        let version = member(member(json, "asset")?, "version")?.as_str();
+        if !version.is_some_and(|version| version.starts_with("2.")) {
+            return Err(format!("unsupported glTF version {version:?}"));
+        }
+
+        let mut bin = bin;
+        let buffers = json
+            .get("buffers")
+            .and_then(Json::as_array)
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| {
+                let data = match buffer.get("uri").and_then(Json::as_str) {
+                    Some(uri) => load_uri(uri, base)?,
+                    None if i == 0 => bin.take().ok_or("buffer 0 has no data")?,
+                    None => return Err(format!("buffer {i} has no URI")),
+                };
+                if data.len() < usize_member(buffer, "byteLength")? {
+                    return Err(format!("buffer {i} is shorter than its `byteLength`"));
+                }
+                Ok(data)
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self { json, buffers })
+    }
+
+    /// Merges the primitives of all meshes.
+    fn mesh(&self) -> Result<Mesh, String> {
+        let meshes = self.json.get("meshes").and_then(Json::as_array);
+        let mut merged: Option<Mesh> = None;
+        for (i, mesh) in meshes.unwrap_or_default().iter().enumerate() {
+            let primitives = member(mesh, "primitives")?.as_array().unwrap_or_default();
+            for (j, primitive) in primitives.iter().enumerate() {
+                let mesh = self
+                    .primitive(primitive)
+                    .map_err(|e| format!("mesh {i}, primitive {j}: {e}"))?;
+                match &mut merged {
+                    Some(merged) => merged.append(mesh)?,
+                    None => merged = Some(mesh),
+                }
+            }
+        }
+        merged.ok_or_else(|| "no meshes".to_owned())
+    }
+
+    fn primitive(&self, primitive: &Json) -> Result<Mesh, String> {
+        let topology = match primitive.get("mode").map_or(Some(4), Json::as_usize) {
+            Some(0) => PrimitiveTopology::PointList,
+            Some(1) => PrimitiveTopology::LineList,
+            Some(3) => PrimitiveTopology::LineStrip,
+            Some(4) => PrimitiveTopology::TriangleList,
+            Some(5) => PrimitiveTopology::TriangleStrip,
+            // Line loops and triangle fans have no wgpu topology
+            mode => return Err(format!("unsupported mode {mode:?}")),
+        };
+
+        let attributes = member(primitive, "attributes")?;
+        let attribute = |name: &str, components: &[usize]| {
+            attributes
+                .get(name)
+                .map(|accessor| {
+                    let accessor = accessor.as_usize().ok_or("invalid accessor index")?;
+                    self.read_floats(accessor, components)
+                        .map_err(|e| format!("`{name}`: {e}"))
+                })
+                .transpose()
+        };
+        let positions = attribute("POSITION", &[3])?.ok_or("missing `POSITION`")?;
+        let normals = attribute("NORMAL", &[3])?;
+        let uvs = attribute("TEXCOORD_0", &[2])?;
+        let colors = attribute("COLOR_0", &[3, 4])?;
+
+        let count = positions.len();
+        if [&normals, &uvs, &colors]
+            .iter()
+            .any(|attribute| attribute.as_ref().is_some_and(|a| a.len() != count))
+        {
+            return Err("attributes with differing counts".to_owned());
+        }
+        let indices = match primitive.get("indices") {
+            Some(accessor) => {
+                let accessor = accessor.as_usize().ok_or("invalid accessor index")?;
+                self.read_indices(accessor)?
+            }
+            None => (0..count as u32).collect(),
+        };
+
+        Ok(Mesh {
+            positions: positions.iter().map(|p| [p[0], p[1], p[2]]).collect(),
+            colors: colors.map(|colors| {
+                colors
+                    .iter()
+                    .map(|c| [c[0], c[1], c[2]].map(linear_to_srgb))
+                    .collect()
+            }),
+            normals: normals.map(|normals| normals.iter().map(|n| [n[0], n[1], n[2]]).collect()),
+            uvs: uvs.map(|uvs| uvs.iter().map(|uv| [uv[0], uv[1]]).collect()),
+            indices,
+            topology,
+        })
+    }
+
+    /// The component type, element count and bytes of each element of an
+    /// accessor.
+    fn elements(&self, index: usize) -> Result<(u32, usize, Vec<&[u8]>), String> {
+        let accessor = element(self.json, "accessors", index)?;
+        if accessor.get("sparse").is_some() {
+            return Err(format!("sparse accessor {index} is not supported"));
+        }
+        let component_type = usize_member(accessor, "componentType")? as u32;
+        let component_size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            other => return Err(format!("invalid component type {other}")),
+        };
+        let components = match member(accessor, "type")?.as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            other => return Err(format!("unsupported accessor type {other:?}")),
+        };
+        let count = usize_member(accessor, "count")?;
+        let element_size = component_size * components;
+
+        let view_index = accessor
+            .get("bufferView")
+            .ok_or_else(|| format!("accessor {index} has no buffer view"))?
+            .as_usize()
+            .ok_or("invalid buffer view index")?;
+        let view = element(self.json, "bufferViews", view_index)?;
+        let buffer = self
+            .buffers
+            .get(usize_member(view, "buffer")?)
+            .ok_or("invalid buffer index")?;
+        // Interleaved attributes have a stride
+        let stride = view
+            .get("byteStride")
+            .and_then(Json::as_usize)
+            .unwrap_or(element_size);
+        let view_offset = view.get("byteOffset").map_or(Some(0), Json::as_usize);
+        let view_length = usize_member(view, "byteLength")?;
+        let view = view_offset
+            .and_then(|offset| buffer.get(offset..offset + view_length))
+            .ok_or_else(|| format!("buffer view {view_index} is out of bounds"))?;
+
+        let offset = accessor
+            .get("byteOffset")
+            .map_or(Some(0), Json::as_usize)
+            .ok_or("invalid byte offset")?;
+        let elements = (0..count)
+            .map(|i| view.get(offset + i * stride..offset + i * stride + element_size))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("accessor {index} is out of bounds"))?;
+        Ok((component_type, components, elements))
+    }
+
+    /// Reads an accessor with one of the given component counts as floats.
+    fn read_floats(&self, index: usize, components: &[usize]) -> Result<Vec<Vec<f32>>, String> {
+        let (component_type, count, elements) = self.elements(index)?;
+        if !components.contains(&count) {
+            return Err(format!("{count} components, expected {components:?}"));
+        }
+        let accessor = element(self.json, "accessors", index)?;
+        let normalized = accessor
+            .get("normalized")
+            .and_then(Json::as_bool)
+            .unwrap_or(false);
+
+        // Integer attributes are normalized, e.g. colors, or converted
+        let convert = |bytes: &[u8]| -> f32 {
+            let (value, max) = match component_type {
+                5120 => (bytes[0] as i8 as f32, i8::MAX as f32),
+                5121 => (bytes[0] as f32, u8::MAX as f32),
+                5122 => (
+                    i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                    i16::MAX as f32,
+                ),
+                5123 => (
+                    u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                    u16::MAX as f32,
+                ),
+                5125 => (u32::from_le_bytes(bytes.try_into().unwrap()) as f32, 1.0),
+                _ => return f32::from_le_bytes(bytes.try_into().unwrap()),
+            };
+            if normalized {
+                (value / max).max(-1.0)
+            } else {
+                value
+            }
+        };
+        let size = elements.first().map_or(0, |element| element.len() / count);
+        Ok(elements
+            .iter()
+            .map(|element| element.chunks(size).map(convert).collect())
+            .collect())
+    }
+
+    /// Reads an accessor of unsigned integer scalars.
+    fn read_indices(&self, index: usize) -> Result<Vec<u32>, String> {
+        let (component_type, count, elements) = self.elements(index)?;
+        if count != 1 {
+            return Err(format!("indices with {count} components"));
+        }
+        elements
+            .iter()
+            .map(|bytes| match component_type {
+                5121 => Ok(bytes[0] as u32),
+                5123 => Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as u32),
+                5125 => Ok(u32::from_le_bytes((*bytes).try_into().unwrap())),
+                other => Err(format!("invalid index component type {other}")),
+            })
+            .collect()
+    }
+}
+
+/// Reads a buffer from a `data:` URI or a file relative to `base`.
+fn load_uri(uri: &str, base: &Path) -> Result<Vec<u8>, String> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, data) = data
+            .split_once(";base64,")
+            .ok_or("only base64 data URIs are supported")?;
+        return decode_base64(data);
+    }
+    let path = base.join(uri);
+    std::fs::read(&path).map_err(|e| format!("failed to read `{}`: {e}", path.display()))
+}
+
+/// Decodes standard base64 with optional padding.
+fn decode_base64(data: &str) -> Result<Vec<u8>, String> {
+    let data = data.trim_end_matches('=').as_bytes();
+    let value = |c: u8| -> Result<u32, String> {
+        Ok(match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(format!("invalid base64 character `{}`", c as char)),
+        } as u32)
+    };
+
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    for chunk in data.chunks(4) {
+        let bits = chunk
+            .iter()
+            .try_fold(0, |bits, &c| Ok::<_, String>(bits << 6 | value(c)?))?;
+        let bytes = match chunk.len() {
+            4 => vec![(bits >> 16) as u8, (bits >> 8) as u8, bits as u8],
+            3 => vec![(bits >> 10) as u8, (bits >> 2) as u8],
+            2 => vec![(bits >> 4) as u8],
+            _ => return Err("truncated base64".to_owned()),
+        };
+        out.extend(bytes);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A triangle with `u16` indices and normalized `u8` colors.
+    fn triangle_buffer() -> Vec<u8> {
+        let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let mut buffer = bytemuck::cast_slice::<f32, u8>(&positions).to_vec();
+        buffer.extend(bytemuck::cast_slice::<u16, u8>(&[0, 1, 2, 0]));
+        buffer.extend([255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255]);
+        buffer
+    }
+
+    fn triangle_json(uri: Option<&str>) -> String {
+        let uri = uri.map_or(String::new(), |uri| format!(r#""uri": "{uri}","#));
+        format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "buffers": [{{{uri} "byteLength": 56}}],
+                "bufferViews": [
+                    {{"buffer": 0, "byteLength": 36}},
+                    {{"buffer": 0, "byteOffset": 36, "byteLength": 6}},
+                    {{"buffer": 0, "byteOffset": 44, "byteLength": 12}}
+                ],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}},
+                    {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}},
+                    {{"bufferView": 2, "componentType": 5121, "normalized": true,
+                      "count": 3, "type": "VEC4"}}
+                ],
+                "meshes": [{{"primitives": [{{
+                    "attributes": {{"POSITION": 0, "COLOR_0": 2}},
+                    "indices": 1
+                }}]}}]
+            }}"#
+        )
+    }
+
+    fn check_triangle(mesh: &Mesh) {
+        assert_eq!(mesh.positions[2], [0.0, 1.0, 0.0]);
+        assert_eq!(mesh.indices, [0, 1, 2]);
+        for (a, b) in mesh.colors.as_ref().unwrap()[1].iter().zip([0.0, 1.0, 0.0]) {
+            assert!((a - b).abs() < 1e-6, "{:?}", mesh.colors);
+        }
+        assert_eq!(mesh.topology, PrimitiveTopology::TriangleList);
+    }
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        bytes
+            .chunks(3)
+            .flat_map(|chunk| {
+                let bits = chunk.iter().fold(0u32, |bits, &b| bits << 8 | b as u32)
+                    << (8 * (3 - chunk.len()));
+                (0..4).map(move |i| {
+                    if i <= chunk.len() {
+                        ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char
+                    } else {
+                        '='
+                    }
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decodes_base64() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGk").unwrap(), b"hi");
+        let bytes = triangle_buffer();
+        assert_eq!(decode_base64(&encode_base64(&bytes)).unwrap(), bytes);
+        assert!(decode_base64("a!==").is_err());
+    }
+
+    #[test]
+    fn parses_embedded_and_binary_files() {
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            encode_base64(&triangle_buffer())
+        );
+        let mesh = parse_gltf(&triangle_json(Some(&uri)), Path::new(".")).unwrap();
+        check_triangle(&mesh);
+
+        let mut json = triangle_json(None).into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let bin = triangle_buffer();
+        let mut glb = Vec::new();
+        for word in [GLB_MAGIC, 2, (28 + json.len() + bin.len()) as u32] {
+            glb.extend(word.to_le_bytes());
+        }
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(CHUNK_JSON.to_le_bytes());
+        glb.extend(json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(CHUNK_BIN.to_le_bytes());
+        glb.extend(bin);
+        let mesh = parse_glb(&glb, Path::new(".")).unwrap();
+        check_triangle(&mesh);
+    }
+
+    #[test]
+    fn rejects_unsupported_modes() {
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            encode_base64(&triangle_buffer())
+        );
+        let json = triangle_json(Some(&uri)).replace(r#""indices": 1"#, r#""mode": 6"#);
+        assert_eq!(
+            parse_gltf(&json, Path::new(".")).unwrap_err(),
+            "mesh 0, primitive 0: unsupported mode Some(6)"
+        );
+    }
+}
@@ -0,0 +1,505 @@
+//! CPU-side geometry of the polygon scenarios.
+//!
+//! A [`Geometry`] is an indexed mesh together with the primitive topology its
+//! indices are laid out for. [`Geometry::validate`] checks the indices against
+//! the topology, as a mismatch (e.g. triangle list indices drawn as a strip)
+//! doesn't raise any wgpu error but silently draws different primitives.
+//!
+//! Geometries of common shapes are generated by [`regular_polygon`] and its
+//! siblings in [`shape`]. Meshes of user-reported issues are loaded from OBJ
+//! and glTF files into a [`Mesh`], which is only supported natively.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod gltf;
+#[cfg(not(target_arch = "wasm32"))]
+mod obj;
+mod shape;
+mod svg;
+
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use wgpu::PrimitiveTopology;
+
+pub(crate) use shape::{circle, rect, regular_polygon, ring, star, Shape, ShapeVertex};
+pub(crate) use svg::{FillRule, SvgPath};
+
+/// Index restarting strips.
+///
+/// Geometries are uploaded with `Uint16` indices when all vertices fit, which
+/// maps this to the 16 bit restart index.
+pub(crate) const RESTART_INDEX: u32 = u32::MAX;
+
+/// An indexed mesh.
+///
+/// Strips may be split into several by [`RESTART_INDEX`].
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Geometry<V> {
+    pub(crate) vertices: Vec<V>,
+    pub(crate) indices: Vec<u32>,
+    /// How the indices form primitives.
+    pub(crate) topology: PrimitiveTopology,
+}
+
+/// A mesh loaded from a file, see [`Mesh::load`].
+///
+/// The optional attributes are either missing or given for every vertex.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mesh {
+    pub(crate) positions: Vec<[f32; 3]>,
+    /// sRGB vertex colors.
+    pub(crate) colors: Option<Vec<[f32; 3]>>,
+    pub(crate) normals: Option<Vec<[f32; 3]>>,
+    /// Texture coordinates with the origin at the top left, as in wgpu.
+    pub(crate) uvs: Option<Vec<[f32; 2]>>,
+    pub(crate) indices: Vec<u32>,
+    pub(crate) topology: PrimitiveTopology,
+}
+
+/// A vertex of a [`Mesh`], converted into the vertex type of the geometry.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MeshVertex {
+    pub(crate) position: [f32; 3],
+    /// The vertex color, or the normal mapped to a color for meshes without
+    /// colors, or white.
+    pub(crate) color: [f32; 3],
+    /// The normal, zero if the mesh has none.
+    pub(crate) normal: [f32; 3],
+    /// The texture coordinates, zero if the mesh has none.
+    pub(crate) uv: [f32; 2],
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Mesh {
+    /// Loads an `.obj`, `.gltf` or `.glb` file.
+    ///
+    /// All meshes in the file are merged into one, which must use a single
+    /// topology.
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let read_to_string = || std::fs::read_to_string(path).map_err(|e| e.to_string());
+        let base = path.parent().unwrap_or(Path::new("."));
+        let mesh = match extension.as_deref() {
+            Some("obj") => read_to_string().and_then(|source| obj::parse(&source)),
+            Some("gltf") => read_to_string().and_then(|source| gltf::parse_gltf(&source, base)),
+            Some("glb") => std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| gltf::parse_glb(&bytes, base)),
+            _ => Err("unknown format, expected `.obj`, `.gltf` or `.glb`".to_owned()),
+        };
+        let mesh = mesh.map_err(|e| format!("Failed to load `{}`: {e}", path.display()))?;
+
+        if mesh.positions.is_empty() {
+            return Err(format!("`{}` contains no vertices", path.display()));
+        }
+        Ok(mesh)
+    }
+
+    /// Moves and scales the mesh to fit into a unit cube, centered at the
+    /// origin in x and y, and starting at `z = 0`, the near clip plane.
+    pub(crate) fn fit_unit_cube(mut self) -> Self {
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for position in &self.positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+        let extent = (0..3).map(|axis| max[axis] - min[axis]).fold(0.0, f32::max);
+        let scale = if extent > 0.0 { 1.0 / extent } else { 1.0 };
+        let origin = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0, min[2]];
+        for position in &mut self.positions {
+            for axis in 0..3 {
+                position[axis] = (position[axis] - origin[axis]) * scale;
+            }
+        }
+        self
+    }
+
+    /// Appends the vertices and primitives of another mesh with the same
+    /// topology.
+    fn append(&mut self, other: Mesh) -> Result<(), String> {
+        if other.topology != self.topology {
+            return Err(format!(
+                "mixed topologies {:?} and {:?}",
+                self.topology, other.topology
+            ));
+        }
+        fn merge<T: Copy>(
+            a: &mut Option<Vec<T>>,
+            a_len: usize,
+            b: Option<Vec<T>>,
+            b_len: usize,
+            default: T,
+        ) {
+            match (a.as_mut(), b) {
+                (Some(a), Some(b)) => a.extend(b),
+                (Some(a), None) => a.extend(std::iter::repeat(default).take(b_len)),
+                (None, Some(b)) => {
+                    let mut merged = vec![default; a_len];
+                    merged.extend(b);
+                    *a = Some(merged);
+                }
+                (None, None) => {}
+            }
+        }
+        let (len, other_len) = (self.positions.len(), other.positions.len());
+        merge(&mut self.colors, len, other.colors, other_len, [1.0; 3]);
+        merge(&mut self.normals, len, other.normals, other_len, [0.0; 3]);
+        merge(&mut self.uvs, len, other.uvs, other_len, [0.0; 2]);
+        self.positions.extend(other.positions);
+
+        if self.topology.is_strip() && !self.indices.is_empty() {
+            self.indices.push(RESTART_INDEX);
+        }
+        let offset = len as u32;
+        self.indices.extend(other.indices.into_iter().map(|index| {
+            if index == RESTART_INDEX {
+                index
+            } else {
+                index + offset
+            }
+        }));
+        Ok(())
+    }
+
+    fn vertices(&self) -> impl Iterator<Item = MeshVertex> + '_ {
+        (0..self.positions.len()).map(|i| {
+            let normal = self.normals.as_ref().map_or([0.0; 3], |normals| normals[i]);
+            let color = match (&self.colors, &self.normals) {
+                (Some(colors), _) => colors[i],
+                (None, Some(_)) => normal.map(|n| n * 0.5 + 0.5),
+                (None, None) => [1.0; 3],
+            };
+            MeshVertex {
+                position: self.positions[i],
+                color,
+                normal,
+                uv: self.uvs.as_ref().map_or([0.0; 2], |uvs| uvs[i]),
+            }
+        })
+    }
+
+    /// Converts the mesh into a geometry of the given vertex type.
+    pub(crate) fn geometry<V: From<MeshVertex>>(&self) -> Geometry<V> {
+        Geometry {
+            vertices: self.vertices().map(V::from).collect(),
+            indices: self.indices.clone(),
+            topology: self.topology,
+        }
+    }
+}
+
+/// Indices which don't fit the topology of a [`Geometry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TopologyError {
+    IndexOutOfBounds {
+        position: usize,
+        index: u32,
+        vertex_count: usize,
+    },
+    /// A list topology with a partial primitive at the end.
+    IncompletePrimitive {
+        topology: PrimitiveTopology,
+        index_count: usize,
+    },
+    /// A strip, or a part of it between restarts, with less indices than a
+    /// single primitive.
+    TooFewIndices {
+        topology: PrimitiveTopology,
+        index_count: usize,
+    },
+    /// A list primitive using the same vertex more than once.
+    DegeneratePrimitive {
+        topology: PrimitiveTopology,
+        primitive: usize,
+    },
+    /// A strip whose indices form a proper triangle list, but degenerate
+    /// triangles when drawn as a strip.
+    ListIndicesAsStrip { degenerate: usize },
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IndexOutOfBounds {
+                position,
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "index {index} at position {position} is out of bounds for {vertex_count} vertices"
+            ),
+            Self::IncompletePrimitive {
+                topology,
+                index_count,
+            } => write!(
+                f,
+                "{index_count} indices are not a multiple of the {} per {topology:?} primitive",
+                vertices_per_primitive(*topology)
+            ),
+            Self::TooFewIndices {
+                topology,
+                index_count,
+            } => write!(
+                f,
+                "{index_count} indices are less than the {} of a single {topology:?} primitive",
+                vertices_per_primitive(*topology)
+            ),
+            Self::DegeneratePrimitive {
+                topology,
+                primitive,
+            } => write!(
+                f,
+                "{topology:?} primitive {primitive} uses the same vertex more than once"
+            ),
+            Self::ListIndicesAsStrip { degenerate } => write!(
+                f,
+                "the indices form a triangle list, but {degenerate} degenerate triangles as a strip"
+            ),
+        }
+    }
+}
+
+/// The number of vertices of a single primitive.
+fn vertices_per_primitive(topology: PrimitiveTopology) -> usize {
+    match topology {
+        PrimitiveTopology::PointList => 1,
+        PrimitiveTopology::LineList | PrimitiveTopology::LineStrip => 2,
+        PrimitiveTopology::TriangleList | PrimitiveTopology::TriangleStrip => 3,
+    }
+}
+
+/// Whether a triangle or line uses the same vertex more than once.
+fn is_degenerate(primitive: &[u32]) -> bool {
+    primitive
+        .iter()
+        .enumerate()
+        .any(|(i, index)| primitive[i + 1..].contains(index))
+}
+
+impl<V> Geometry<V> {
+    /// The smallest index format addressing all vertices.
+    ///
+    /// `0xFFFF` restarts strips in `Uint16`, so at most `0xFFFF` vertices fit.
+    pub(crate) fn index_format(&self) -> wgpu::IndexFormat {
+        if self.vertices.len() <= u16::MAX as usize {
+            wgpu::IndexFormat::Uint16
+        } else {
+            wgpu::IndexFormat::Uint32
+        }
+    }
+
+    /// The indices in the [`index_format`](Self::index_format), ready for
+    /// upload.
+    pub(crate) fn index_bytes(&self) -> Vec<u8> {
+        match self.index_format() {
+            wgpu::IndexFormat::Uint16 => {
+                let indices = self
+                    .indices
+                    .iter()
+                    .map(|&index| {
+                        if index == RESTART_INDEX {
+                            u16::MAX
+                        } else {
+                            index as u16
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                bytemuck::cast_slice(&indices).to_vec()
+            }
+            wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(&self.indices).to_vec(),
+        }
+    }
+
+    /// Appends the vertices and primitives of another geometry, e.g. the
+    /// stroke of a fill.
+    ///
+    /// Both must have the same list topology.
+    pub(crate) fn append(&mut self, other: Geometry<V>) {
+        assert!(
+            !self.topology.is_strip() && self.topology == other.topology,
+            "can't append {:?} to {:?}",
+            other.topology,
+            self.topology
+        );
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.indices
+            .extend(other.indices.into_iter().map(|index| index + offset));
+    }
+
+    /// Checks the indices against the vertex count and the topology.
+    pub(crate) fn validate(&self) -> Result<(), TopologyError> {
+        let topology = self.topology;
+        let vertex_count = self.vertices.len();
+        let is_restart = |index: u32| topology.is_strip() && index == RESTART_INDEX;
+        if let Some((position, &index)) = self
+            .indices
+            .iter()
+            .enumerate()
+            .find(|(_, &index)| index as usize >= vertex_count && !is_restart(index))
+        {
+            return Err(TopologyError::IndexOutOfBounds {
+                position,
+                index,
+                vertex_count,
+            });
+        }
+
+        let index_count = self.indices.len();
+        let per_primitive = vertices_per_primitive(topology);
+        if topology.is_strip() {
+            let strips = self.indices.split(|&index| index == RESTART_INDEX);
+            if let Some(strip) = strips.clone().find(|strip| strip.len() < per_primitive) {
+                return Err(TopologyError::TooFewIndices {
+                    topology,
+                    index_count: strip.len(),
+                });
+            }
+
+            // Degenerate triangles are a common way to join strips, so they
+            // are only reported if the indices fit a list much better
+            if topology == PrimitiveTopology::TriangleStrip
+                && strips.count() == 1
+                && index_count % 3 == 0
+            {
+                let degenerate = self
+                    .indices
+                    .windows(3)
+                    .filter(|triangle| is_degenerate(triangle))
+                    .count();
+                let list_is_clean = !self.indices.chunks(3).any(is_degenerate);
+                if degenerate > 0 && list_is_clean {
+                    return Err(TopologyError::ListIndicesAsStrip { degenerate });
+                }
+            }
+        } else {
+            if index_count % per_primitive != 0 {
+                return Err(TopologyError::IncompletePrimitive {
+                    topology,
+                    index_count,
+                });
+            }
+            if let Some(primitive) = self.indices.chunks(per_primitive).position(is_degenerate) {
+                return Err(TopologyError::DegeneratePrimitive {
+                    topology,
+                    primitive,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(indices: &[u32], topology: PrimitiveTopology) -> Geometry<()> {
+        Geometry {
+            vertices: vec![(); 6],
+            indices: indices.to_vec(),
+            topology,
+        }
+    }
+
+    /// A hexagon as a triangle fan laid out as a list.
+    const HEXAGON_LIST: &[u32] = &[0, 1, 2, 0, 2, 3, 0, 3, 4, 0, 4, 5];
+
+    #[test]
+    fn detects_list_indices_drawn_as_strip() {
+        use PrimitiveTopology::*;
+
+        assert_eq!(geometry(HEXAGON_LIST, TriangleList).validate(), Ok(()));
+        assert_eq!(
+            geometry(HEXAGON_LIST, TriangleStrip).validate(),
+            Err(TopologyError::ListIndicesAsStrip { degenerate: 3 })
+        );
+        assert_eq!(
+            geometry(&[1, 2, 0, 3, 5, 4], TriangleStrip).validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn picks_the_index_format() {
+        let small = geometry(
+            &[0, 1, 2, RESTART_INDEX, 3, 4, 5],
+            PrimitiveTopology::TriangleStrip,
+        );
+        assert_eq!(small.index_format(), wgpu::IndexFormat::Uint16);
+        assert_eq!(
+            small.index_bytes(),
+            bytemuck::cast_slice::<u16, u8>(&[0, 1, 2, u16::MAX, 3, 4, 5])
+        );
+
+        let large = Geometry {
+            vertices: vec![(); u16::MAX as usize + 1],
+            indices: vec![0, 1, u16::MAX as u32],
+            topology: PrimitiveTopology::TriangleList,
+        };
+        assert_eq!(large.index_format(), wgpu::IndexFormat::Uint32);
+        assert_eq!(large.index_bytes().len(), 12);
+    }
+
+    #[test]
+    fn loads_the_test_assets() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets");
+        for file in ["cube.obj", "quad.gltf"] {
+            let mesh = Mesh::load(&assets.join(file)).unwrap();
+            let geometry = mesh.geometry::<MeshVertex>();
+            assert_eq!(geometry.validate(), Ok(()), "{file}");
+        }
+
+        let error = Mesh::load(&assets.join("missing.obj")).unwrap_err();
+        assert!(error.contains("missing.obj"), "{error}");
+    }
+
+    #[test]
+    fn checks_index_counts() {
+        use PrimitiveTopology::*;
+
+        assert_eq!(
+            geometry(&[0, 1, 2, 3], TriangleList).validate(),
+            Err(TopologyError::IncompletePrimitive {
+                topology: TriangleList,
+                index_count: 4
+            })
+        );
+        assert_eq!(
+            geometry(&[0], LineStrip).validate(),
+            Err(TopologyError::TooFewIndices {
+                topology: LineStrip,
+                index_count: 1
+            })
+        );
+        assert_eq!(
+            geometry(&[0, 1, 2, 2], LineList).validate(),
+            Err(TopologyError::DegeneratePrimitive {
+                topology: LineList,
+                primitive: 1
+            })
+        );
+        assert_eq!(
+            geometry(&[0, 6], PointList).validate(),
+            Err(TopologyError::IndexOutOfBounds {
+                position: 1,
+                index: 6,
+                vertex_count: 6
+            })
+        );
+        assert_eq!(
+            geometry(&[0, 1, 2, 3, 4, 5, 0], LineStrip).validate(),
+            Ok(())
+        );
+    }
+}
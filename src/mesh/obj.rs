@@ -0,0 +1,195 @@
+//! Wavefront OBJ parsing.
+//!
+//! Supports vertices with optional colors (`v x y z r g b`), texture
+//! coordinates, normals and polygonal faces, which are triangulated as fans.
+//! Lines, points, groups and materials are ignored.
+
+use std::collections::HashMap;
+
+use wgpu::PrimitiveTopology;
+
+use super::Mesh;
+
+/// The `v/vt/vn` indices of a face corner, zero based.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Parses the source of an OBJ file.
+pub(super) fn parse(source: &str) -> Result<Mesh, String> {
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut faces = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let Some((keyword, args)) = line
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .split_first()
+            .map(|(keyword, args)| (*keyword, args.to_vec()))
+        else {
+            continue;
+        };
+        let error = |msg: String| format!("line {}: {msg}", number + 1);
+        let values = || {
+            args.iter()
+                .map(|word| {
+                    word.parse::<f32>()
+                        .map_err(|_| error(format!("invalid number `{word}`")))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        match keyword {
+            "v" => match values()?[..] {
+                [x, y, z] | [x, y, z, _] => positions.push([x, y, z]),
+                [x, y, z, r, g, b] => {
+                    positions.push([x, y, z]);
+                    colors.resize(positions.len() - 1, [1.0; 3]);
+                    colors.push([r, g, b]);
+                }
+                _ => return Err(error("expected 3 coordinates and an optional color".into())),
+            },
+            // Flipped to the top left origin of wgpu
+            "vt" => match values()?[..] {
+                [u] => uvs.push([u, 1.0]),
+                [u, v, ..] => uvs.push([u, 1.0 - v]),
+                _ => return Err(error("expected texture coordinates".into())),
+            },
+            "vn" => match values()?[..] {
+                [x, y, z] => normals.push([x, y, z]),
+                _ => return Err(error("expected 3 normal components".into())),
+            },
+            "f" => {
+                let counts = (positions.len(), uvs.len(), normals.len());
+                let corners = args
+                    .iter()
+                    .map(|word| corner(word, counts))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                if corners.len() < 3 {
+                    return Err(error("a face needs at least 3 vertices".into()));
+                }
+                faces.push(corners);
+            }
+            _ => {}
+        }
+    }
+
+    // Vertices are unique combinations of position, uv and normal
+    let mut vertices = HashMap::new();
+    let mut corners = Vec::new();
+    let mut indices = Vec::new();
+    for face in &faces {
+        let face = face
+            .iter()
+            .map(|&corner| {
+                *vertices.entry(corner).or_insert_with(|| {
+                    corners.push(corner);
+                    corners.len() as u32 - 1
+                })
+            })
+            .collect::<Vec<_>>();
+        indices.extend((1..face.len() - 1).flat_map(|i| [face[0], face[i], face[i + 1]]));
+    }
+
+    let has_uvs = corners.iter().any(|(_, uv, _)| uv.is_some());
+    let has_normals = corners.iter().any(|(_, _, normal)| normal.is_some());
+    colors.resize(positions.len(), [1.0; 3]);
+    Ok(Mesh {
+        positions: corners.iter().map(|&(v, _, _)| positions[v]).collect(),
+        colors: (!colors.iter().all(|&color| color == [1.0; 3]))
+            .then(|| corners.iter().map(|&(v, _, _)| colors[v]).collect()),
+        normals: has_normals.then(|| {
+            corners
+                .iter()
+                .map(|&(_, _, n)| n.map_or([0.0; 3], |n| normals[n]))
+                .collect()
+        }),
+        uvs: has_uvs.then(|| {
+            corners
+                .iter()
+                .map(|&(_, uv, _)| uv.map_or([0.0; 2], |uv| uvs[uv]))
+                .collect()
+        }),
+        indices,
+        topology: PrimitiveTopology::TriangleList,
+    })
+}
+
+/// Parses a face corner `v`, `v/vt`, `v//vn` or `v/vt/vn` given the counts
+/// of the elements defined so far.
+fn corner(word: &str, (positions, uvs, normals): (usize, usize, usize)) -> Result<Corner, String> {
+    let mut parts = word.split('/');
+    let mut index = |count: usize, what: &str| -> Result<Option<usize>, String> {
+        match parts.next() {
+            None | Some("") => Ok(None),
+            Some(part) => {
+                let index = part
+                    .parse::<isize>()
+                    .map_err(|_| format!("invalid {what} index `{part}`"))?;
+                // One based, or negative relative to the end
+                let resolved = match index {
+                    1.. => index as usize - 1,
+                    ..=-1 => count.wrapping_sub(index.unsigned_abs()),
+                    0 => usize::MAX,
+                };
+                if resolved >= count {
+                    return Err(format!("{what} index {index} is out of bounds"));
+                }
+                Ok(Some(resolved))
+            }
+        }
+    };
+    let position =
+        index(positions, "vertex")?.ok_or_else(|| format!("missing vertex index in `{word}`"))?;
+    Ok((
+        position,
+        index(uvs, "texture coordinate")?,
+        index(normals, "normal")?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_faces() {
+        let mesh = parse(
+            "# A quad
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0 1 0 0
+            v 0 1 0
+            vt 0 0
+            vn 0 0 1
+            f 1/1/1 2/1/1 3/1/1 -1/1/-1",
+        )
+        .unwrap();
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.positions[3], [0.0, 1.0, 0.0]);
+        assert_eq!(mesh.uvs.unwrap()[0], [0.0, 1.0]);
+        assert_eq!(mesh.normals.unwrap()[0], [0.0, 0.0, 1.0]);
+        assert_eq!(
+            mesh.colors.unwrap(),
+            [[1.0; 3], [1.0; 3], [1.0, 0.0, 0.0], [1.0; 3]]
+        );
+
+        // Shared corners are deduplicated, colors and uvs are optional
+        let mesh = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nf 1 2 3\nf 3 2 4").unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, [0, 1, 2, 2, 1, 3]);
+        assert_eq!((mesh.colors, mesh.uvs, mesh.normals), (None, None, None));
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        assert_eq!(
+            parse("v 0 0 0\nf 1 2 3").unwrap_err(),
+            "line 2: vertex index 2 is out of bounds"
+        );
+        assert_eq!(parse("v 0 x 0").unwrap_err(), "line 1: invalid number `x`");
+    }
+}
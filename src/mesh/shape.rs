@@ -0,0 +1,306 @@
+//! Procedural 2D shapes.
+//!
+//! The shapes lie in the `z = 0` plane, centered at the origin, with their
+//! outline in counter-clockwise order starting on the positive x axis. They
+//! are turned into a [`Geometry`] of any vertex type for any topology:
+//! triangle topologies fill the shape, line topologies draw its outline and
+//! `PointList` its vertices.
+
+use std::f32::consts::TAU;
+
+use wgpu::PrimitiveTopology;
+
+use super::{Geometry, RESTART_INDEX};
+
+/// Number of segments of [`circle`].
+const CIRCLE_SEGMENTS: usize = 48;
+
+/// A generated vertex, converted into the vertex type of the geometry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ShapeVertex {
+    pub(crate) position: [f32; 3],
+    /// The sRGB color, white unless set by [`Shape::with_color`] or
+    /// [`Shape::with_gradient`].
+    pub(crate) color: [f32; 3],
+}
+
+/// How the vertices of a shape are connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// A convex outline, filled as a fan around the first vertex.
+    Convex,
+    /// A center vertex followed by an outline visible from it.
+    Centered,
+    /// An outer outline followed by an inner one with the same number of
+    /// vertices.
+    Ring,
+}
+
+/// A generated shape, see [`regular_polygon`], [`rect`], [`circle`],
+/// [`ring`] and [`star`].
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Shape {
+    kind: Kind,
+    positions: Vec<[f32; 2]>,
+    colors: Vec<[f32; 3]>,
+}
+
+/// A regular polygon with `sides` corners at `radius`.
+///
+/// The first corner is on the positive x axis.
+pub(crate) fn regular_polygon(sides: usize, radius: f32) -> Shape {
+    assert!(sides >= 3, "a polygon needs at least 3 sides, not {sides}");
+    Shape::new(Kind::Convex, outline(sides, radius))
+}
+
+/// An axis-aligned rectangle of the given size.
+pub(crate) fn rect(width: f32, height: f32) -> Shape {
+    let (x, y) = (width / 2.0, height / 2.0);
+    Shape::new(Kind::Convex, vec![[x, -y], [x, y], [-x, y], [-x, -y]])
+}
+
+/// A circle approximated by a regular polygon.
+pub(crate) fn circle(radius: f32) -> Shape {
+    regular_polygon(CIRCLE_SEGMENTS, radius)
+}
+
+/// An annulus between the two radii, approximated with `segments` segments.
+pub(crate) fn ring(inner_radius: f32, outer_radius: f32, segments: usize) -> Shape {
+    assert!(
+        segments >= 3,
+        "a ring needs at least 3 segments, not {segments}"
+    );
+    let mut positions = outline(segments, outer_radius);
+    positions.extend(outline(segments, inner_radius));
+    Shape::new(Kind::Ring, positions)
+}
+
+/// A star with `points` tips at `outer_radius` and the notches between them
+/// at `inner_radius`.
+pub(crate) fn star(points: usize, inner_radius: f32, outer_radius: f32) -> Shape {
+    assert!(points >= 2, "a star needs at least 2 points, not {points}");
+    let step = TAU / (2 * points) as f32;
+    let mut positions = vec![[0.0, 0.0]];
+    positions.extend((0..2 * points).map(|i| {
+        let radius = if i % 2 == 0 {
+            outer_radius
+        } else {
+            inner_radius
+        };
+        let angle = i as f32 * step;
+        [radius * angle.cos(), radius * angle.sin()]
+    }));
+    Shape::new(Kind::Centered, positions)
+}
+
+/// `count` points evenly spaced on a circle, counter-clockwise from the
+/// positive x axis.
+fn outline(count: usize, radius: f32) -> Vec<[f32; 2]> {
+    (0..count)
+        .map(|i| {
+            let angle = i as f32 * TAU / count as f32;
+            [radius * angle.cos(), radius * angle.sin()]
+        })
+        .collect()
+}
+
+/// The color wheel at the angle of `position`, as the `WithColor` gradient.
+///
+/// The components can be slightly negative, which the render target clamps.
+fn hue(position: [f32; 2]) -> [f32; 3] {
+    if position == [0.0, 0.0] {
+        return [1.0 / 3.0; 3];
+    }
+    let angle = position[1].atan2(position[0]);
+    [0.0, 1.0, 2.0].map(|i| 1.0 / 3.0 + 2.0 / 3.0 * (angle - i * TAU / 3.0).cos())
+}
+
+impl Shape {
+    fn new(kind: Kind, positions: Vec<[f32; 2]>) -> Self {
+        let colors = vec![[1.0; 3]; positions.len()];
+        Self {
+            kind,
+            positions,
+            colors,
+        }
+    }
+
+    /// Colors all vertices with `color`.
+    pub(crate) fn with_color(mut self, color: [f32; 3]) -> Self {
+        self.colors.fill(color);
+        self
+    }
+
+    /// Colors the vertices by their angle around the center.
+    pub(crate) fn with_gradient(mut self) -> Self {
+        self.colors = self.positions.iter().copied().map(hue).collect();
+        self
+    }
+
+    fn vertices(&self) -> impl Iterator<Item = ShapeVertex> + '_ {
+        self.positions
+            .iter()
+            .zip(&self.colors)
+            .map(|(&[x, y], &color)| ShapeVertex {
+                position: [x, y, 0.0],
+                color,
+            })
+    }
+
+    /// The indices of the outline loops.
+    fn loops(&self) -> Vec<Vec<u32>> {
+        let count = self.positions.len() as u32;
+        match self.kind {
+            Kind::Convex => vec![(0..count).collect()],
+            Kind::Centered => vec![(1..count).collect()],
+            Kind::Ring => vec![(0..count / 2).collect(), (count / 2..count).collect()],
+        }
+    }
+
+    /// The indices filling the shape with a triangle list.
+    fn triangle_list(&self) -> Vec<u32> {
+        let count = self.positions.len() as u32;
+        match self.kind {
+            Kind::Convex => (1..count - 1).flat_map(|i| [0, i, i + 1]).collect(),
+            Kind::Centered => (1..count)
+                .flat_map(|i| [0, i, if i + 1 < count { i + 1 } else { 1 }])
+                .collect(),
+            Kind::Ring => {
+                let segments = count / 2;
+                (0..segments)
+                    .flat_map(|i| {
+                        let next = (i + 1) % segments;
+                        let (outer, inner) = ([i, next], [i + segments, next + segments]);
+                        [outer[0], inner[0], outer[1], outer[1], inner[0], inner[1]]
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// The indices filling the shape with a triangle strip.
+    fn triangle_strip(&self) -> Vec<u32> {
+        let count = self.positions.len() as u32;
+        match self.kind {
+            // Zig-zag between both sides of the outline
+            Kind::Convex => {
+                let mut indices = vec![0];
+                let (mut low, mut high) = (1, count - 1);
+                while low <= high {
+                    indices.push(low);
+                    if low != high {
+                        indices.push(high);
+                    }
+                    low += 1;
+                    high -= 1;
+                }
+                indices
+            }
+            // A fan isn't a strip, so each triangle is a strip of its own
+            Kind::Centered => self
+                .triangle_list()
+                .chunks(3)
+                .collect::<Vec<_>>()
+                .join(&RESTART_INDEX),
+            Kind::Ring => {
+                let segments = count / 2;
+                (0..=segments)
+                    .flat_map(|i| [i % segments, i % segments + segments])
+                    .collect()
+            }
+        }
+    }
+
+    /// Generates the geometry for the given topology.
+    pub(crate) fn geometry<V: From<ShapeVertex>>(
+        &self,
+        topology: PrimitiveTopology,
+    ) -> Geometry<V> {
+        let indices = match topology {
+            PrimitiveTopology::PointList => (0..self.positions.len() as u32).collect(),
+            PrimitiveTopology::LineList => self
+                .loops()
+                .iter()
+                .flat_map(|outline| {
+                    let next = outline.iter().cycle().skip(1);
+                    outline.iter().zip(next).flat_map(|(&a, &b)| [a, b])
+                })
+                .collect(),
+            PrimitiveTopology::LineStrip => self
+                .loops()
+                .into_iter()
+                .map(|mut outline| {
+                    outline.push(outline[0]);
+                    outline
+                })
+                .collect::<Vec<_>>()
+                .join(&RESTART_INDEX),
+            PrimitiveTopology::TriangleList => self.triangle_list(),
+            PrimitiveTopology::TriangleStrip => self.triangle_strip(),
+        };
+
+        Geometry {
+            vertices: self.vertices().map(V::from).collect(),
+            indices,
+            topology,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOPOLOGIES: [PrimitiveTopology; 5] = [
+        PrimitiveTopology::PointList,
+        PrimitiveTopology::LineList,
+        PrimitiveTopology::LineStrip,
+        PrimitiveTopology::TriangleList,
+        PrimitiveTopology::TriangleStrip,
+    ];
+
+    #[test]
+    fn shapes_are_valid_for_all_topologies() {
+        let shapes = [
+            regular_polygon(3, 1.0),
+            regular_polygon(6, 1.0),
+            rect(2.0, 1.0),
+            circle(1.0),
+            ring(0.5, 1.0, 12),
+            star(5, 0.4, 1.0),
+        ];
+        for shape in &shapes {
+            for topology in TOPOLOGIES {
+                let geometry = shape.geometry::<ShapeVertex>(topology);
+                assert_eq!(geometry.validate(), Ok(()), "{shape:?} as {topology:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn hexagon_matches_the_original_table() {
+        let hexagon = regular_polygon(6, 0.5)
+            .with_gradient()
+            .geometry::<ShapeVertex>(PrimitiveTopology::TriangleList);
+        assert_eq!(hexagon.indices, [0, 1, 2, 0, 2, 3, 0, 3, 4, 0, 4, 5]);
+
+        let second = hexagon.vertices[1];
+        let expected = [0.25, 0.5 * 3f32.sqrt() / 2.0, 0.0];
+        for (a, b) in second.position.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-6, "{:?}", second.position);
+        }
+        for (a, b) in second.color.iter().zip([0.667, 0.667, -0.333]) {
+            assert!((a - b).abs() < 1e-3, "{:?}", second.color);
+        }
+    }
+
+    #[test]
+    fn strips_cover_the_same_triangles() {
+        let hexagon = regular_polygon(6, 1.0);
+        let strip = hexagon.geometry::<ShapeVertex>(PrimitiveTopology::TriangleStrip);
+        assert_eq!(strip.indices, [0, 1, 5, 2, 4, 3]);
+
+        let ring = ring(0.5, 1.0, 4).geometry::<ShapeVertex>(PrimitiveTopology::TriangleStrip);
+        assert_eq!(ring.indices, [0, 4, 1, 5, 2, 6, 3, 7, 0, 4]);
+    }
+}
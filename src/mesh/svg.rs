@@ -0,0 +1,776 @@
+//! Tessellation of SVG path data.
+//!
+//! [`SvgPath::parse`] reads the `d` attribute of an SVG `<path>` with all
+//! commands (move, line, horizontal and vertical line, quadratic and cubic
+//! Béziers with their smooth variants, elliptical arcs and close path). Arcs
+//! are converted to cubic Béziers, which are flattened to line segments when
+//! tessellating.
+//!
+//! Fills are split into horizontal trapezoids between all vertices and edge
+//! intersections, which handles holes and self-intersections with either
+//! [`FillRule`]. Strokes are a quad per segment with beveled joins and butt
+//! caps. Both result in a `TriangleList` [`Geometry`] for the polygon
+//! pipelines.
+
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use wgpu::PrimitiveTopology;
+
+use super::{Geometry, ShapeVertex};
+
+/// Maximum distance between a curve and its flattened line segments, in the
+/// unit square of [`SvgPath::in_view_box`].
+const TOLERANCE: f32 = 0.001;
+
+/// Upper bound of the line segments per curve, against huge control points.
+const MAX_CURVE_SEGMENTS: usize = 256;
+
+type Point = [f32; 2];
+
+/// A path segment from the end of the previous one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Segment {
+    Line(Point),
+    Quadratic(Point, Point),
+    Cubic(Point, Point, Point),
+}
+
+impl Segment {
+    fn end(self) -> Point {
+        match self {
+            Self::Line(end) | Self::Quadratic(_, end) | Self::Cubic(_, _, end) => end,
+        }
+    }
+
+    fn map_points(self, f: impl Fn(Point) -> Point) -> Self {
+        match self {
+            Self::Line(end) => Self::Line(f(end)),
+            Self::Quadratic(control, end) => Self::Quadratic(f(control), f(end)),
+            Self::Cubic(c1, c2, end) => Self::Cubic(f(c1), f(c2), f(end)),
+        }
+    }
+}
+
+/// A connected part of a path, started by a move command.
+#[derive(Debug, Clone, PartialEq)]
+struct Subpath {
+    start: Point,
+    segments: Vec<Segment>,
+    closed: bool,
+}
+
+/// Which regions enclosed by a path are filled, as the SVG `fill-rule`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+impl FillRule {
+    fn is_inside(self, winding: i32) -> bool {
+        match self {
+            Self::NonZero => winding != 0,
+            Self::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+/// Parsed SVG path data.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SvgPath {
+    subpaths: Vec<Subpath>,
+}
+
+fn add(a: Point, b: Point) -> Point {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: Point, s: f32) -> Point {
+    [a[0] * s, a[1] * s]
+}
+
+fn length(a: Point) -> f32 {
+    a[0].hypot(a[1])
+}
+
+/// Reflects `control` at `point`, for the smooth curve commands.
+fn reflect(control: Point, point: Point) -> Point {
+    sub(scale(point, 2.0), control)
+}
+
+/// Tokenizer of path data.
+struct Parser<'a> {
+    data: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: &str) -> String {
+        format!("{msg} at byte {}", self.pos)
+    }
+
+    fn skip_separators(&mut self) {
+        let bytes = self.data.as_bytes();
+        while bytes
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace() || *b == b',')
+        {
+            self.pos += 1;
+        }
+    }
+
+    /// The next command letter, if the next token is one.
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let byte = *self.data.as_bytes().get(self.pos)?;
+        byte.is_ascii_alphabetic().then(|| {
+            self.pos += 1;
+            byte
+        })
+    }
+
+    /// Whether a number follows, i.e. the previous command repeats.
+    fn has_number(&mut self) -> bool {
+        self.skip_separators();
+        self.data
+            .as_bytes()
+            .get(self.pos)
+            .is_some_and(|b| matches!(b, b'0'..=b'9' | b'-' | b'+' | b'.'))
+    }
+
+    fn number(&mut self) -> Result<f32, String> {
+        self.skip_separators();
+        let bytes = self.data.as_bytes();
+        let start = self.pos;
+        let digits = |pos: &mut usize| {
+            while bytes.get(*pos).is_some_and(u8::is_ascii_digit) {
+                *pos += 1;
+            }
+        };
+
+        let mut end = start;
+        if matches!(bytes.get(end), Some(b'-' | b'+')) {
+            end += 1;
+        }
+        digits(&mut end);
+        // `1.5.5` are the two numbers `1.5` and `.5`
+        if bytes.get(end) == Some(&b'.') {
+            end += 1;
+            digits(&mut end);
+        }
+        if matches!(bytes.get(end), Some(b'e' | b'E')) {
+            let mut exponent = end + 1;
+            if matches!(bytes.get(exponent), Some(b'-' | b'+')) {
+                exponent += 1;
+            }
+            if bytes.get(exponent).is_some_and(u8::is_ascii_digit) {
+                digits(&mut exponent);
+                end = exponent;
+            }
+        }
+
+        let number = self.data[start..end]
+            .parse()
+            .map_err(|_| self.error("expected a number"))?;
+        self.pos = end;
+        Ok(number)
+    }
+
+    fn point(&mut self) -> Result<Point, String> {
+        Ok([self.number()?, self.number()?])
+    }
+
+    /// An arc flag, which may be followed by the next number without a
+    /// separator.
+    fn flag(&mut self) -> Result<bool, String> {
+        self.skip_separators();
+        let flag = match self.data.as_bytes().get(self.pos) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(self.error("expected a flag")),
+        };
+        self.pos += 1;
+        Ok(flag)
+    }
+}
+
+impl SvgPath {
+    /// Parses the path data of the `d` attribute.
+    pub(crate) fn parse(data: &str) -> Result<Self, String> {
+        let mut parser = Parser { data, pos: 0 };
+        let mut subpaths: Vec<Subpath> = Vec::new();
+        let mut current = [0.0; 2];
+        // The control point of the previous curve, for the smooth commands
+        let mut last_quadratic = None;
+        let mut last_cubic = None;
+        let mut command = None;
+
+        loop {
+            let next = match parser.command() {
+                Some(next) => next,
+                // Repeated arguments repeat the previous command, moves
+                // continue as lines
+                None if command.is_some() && parser.has_number() => match command {
+                    Some(b'M') => b'L',
+                    Some(b'm') => b'l',
+                    // Closing takes no arguments to repeat
+                    Some(b'Z' | b'z') => return Err(parser.error("expected a command")),
+                    _ => command.unwrap(),
+                },
+                None if parser.pos == data.len() => break,
+                None => return Err(parser.error("expected a command")),
+            };
+            command = Some(next);
+            let relative = next.is_ascii_lowercase();
+            let origin = if relative { current } else { [0.0; 2] };
+
+            // Segments other than moves continue the current subpath, which
+            // restarts at its start after a close
+            if !matches!(next, b'M' | b'm') {
+                match subpaths.last() {
+                    Some(subpath) if !subpath.closed => {}
+                    Some(subpath) => {
+                        let start = subpath.start;
+                        subpaths.push(Subpath {
+                            start,
+                            segments: Vec::new(),
+                            closed: false,
+                        });
+                    }
+                    None => return Err(parser.error("path data must start with a move")),
+                }
+            }
+
+            let segment = match next.to_ascii_uppercase() {
+                b'M' => {
+                    current = add(origin, parser.point()?);
+                    subpaths.push(Subpath {
+                        start: current,
+                        segments: Vec::new(),
+                        closed: false,
+                    });
+                    None
+                }
+                b'L' => Some(Segment::Line(add(origin, parser.point()?))),
+                b'H' => Some(Segment::Line([origin[0] + parser.number()?, current[1]])),
+                b'V' => Some(Segment::Line([current[0], origin[1] + parser.number()?])),
+                b'Q' => {
+                    let control = add(origin, parser.point()?);
+                    Some(Segment::Quadratic(control, add(origin, parser.point()?)))
+                }
+                b'T' => {
+                    let control = last_quadratic.map_or(current, |c| reflect(c, current));
+                    Some(Segment::Quadratic(control, add(origin, parser.point()?)))
+                }
+                b'C' => {
+                    let c1 = add(origin, parser.point()?);
+                    let c2 = add(origin, parser.point()?);
+                    Some(Segment::Cubic(c1, c2, add(origin, parser.point()?)))
+                }
+                b'S' => {
+                    let c1 = last_cubic.map_or(current, |c| reflect(c, current));
+                    let c2 = add(origin, parser.point()?);
+                    Some(Segment::Cubic(c1, c2, add(origin, parser.point()?)))
+                }
+                b'A' => {
+                    let radii = [parser.number()?.abs(), parser.number()?.abs()];
+                    let rotation = parser.number()?.to_radians();
+                    let large_arc = parser.flag()?;
+                    let sweep = parser.flag()?;
+                    let end = add(origin, parser.point()?);
+                    let subpath = subpaths.last_mut().unwrap();
+                    subpath
+                        .segments
+                        .extend(arc(current, radii, rotation, large_arc, sweep, end));
+                    current = end;
+                    None
+                }
+                b'Z' => {
+                    let subpath = subpaths.last_mut().unwrap();
+                    subpath.closed = true;
+                    current = subpath.start;
+                    None
+                }
+                _ => {
+                    parser.pos -= 1;
+                    return Err(parser.error(&format!("unknown command `{}`", next as char)));
+                }
+            };
+
+            last_quadratic = None;
+            last_cubic = None;
+            if let Some(segment) = segment {
+                match segment {
+                    Segment::Quadratic(control, _) => last_quadratic = Some(control),
+                    Segment::Cubic(_, c2, _) => last_cubic = Some(c2),
+                    Segment::Line(_) => {}
+                }
+                current = segment.end();
+                subpaths.last_mut().unwrap().segments.push(segment);
+            }
+        }
+
+        Ok(Self { subpaths })
+    }
+
+    /// Maps the `[min_x, min_y, width, height]` view box of the SVG to the
+    /// unit square centered at the origin, flipping the y axis to point up.
+    pub(crate) fn in_view_box(mut self, view_box: [f32; 4]) -> Self {
+        let [min_x, min_y, width, height] = view_box;
+        let size = width.max(height);
+        let center = [min_x + width / 2.0, min_y + height / 2.0];
+        let map = |p: Point| [(p[0] - center[0]) / size, (center[1] - p[1]) / size];
+        for subpath in &mut self.subpaths {
+            subpath.start = map(subpath.start);
+            for segment in &mut subpath.segments {
+                *segment = segment.map_points(map);
+            }
+        }
+        self
+    }
+
+    /// The subpaths flattened to polylines, with their closed flag.
+    fn polylines(&self) -> Vec<(Vec<Point>, bool)> {
+        self.subpaths
+            .iter()
+            .map(|subpath| {
+                let mut points = vec![subpath.start];
+                for &segment in &subpath.segments {
+                    let start = *points.last().unwrap();
+                    flatten(start, segment, &mut points);
+                }
+                points.dedup();
+                if subpath.closed && points.len() > 1 && points.first() == points.last() {
+                    points.pop();
+                }
+                (points, subpath.closed)
+            })
+            .collect()
+    }
+
+    /// Tessellates the area enclosed by the path, with all subpaths
+    /// implicitly closed.
+    pub(crate) fn fill<V: From<ShapeVertex>>(
+        &self,
+        rule: FillRule,
+        color: [f32; 3],
+    ) -> Geometry<V> {
+        // The edges pointing up count +1 for the winding number
+        let edges = self
+            .polylines()
+            .into_iter()
+            .flat_map(|(points, _)| {
+                let next = points.clone().into_iter().cycle().skip(1);
+                points.into_iter().zip(next).collect::<Vec<_>>()
+            })
+            .filter(|(a, b)| a[1] != b[1])
+            .map(|(a, b)| if a[1] < b[1] { (a, b, 1) } else { (b, a, -1) })
+            .collect::<Vec<_>>();
+
+        // Bands between all vertices and intersections contain no crossings
+        let mut ys = edges
+            .iter()
+            .flat_map(|(a, b, _)| [a[1], b[1]])
+            .collect::<Vec<_>>();
+        for (i, &(a, b, _)) in edges.iter().enumerate() {
+            for &(c, d, _) in &edges[i + 1..] {
+                if let Some(y) = intersection_y(a, b, c, d) {
+                    ys.push(y);
+                }
+            }
+        }
+        ys.sort_by(f32::total_cmp);
+        ys.dedup();
+
+        let x_at = |(a, b, _): &(Point, Point, i32), y: f32| {
+            a[0] + (b[0] - a[0]) * (y - a[1]) / (b[1] - a[1])
+        };
+        let mut builder = Builder::new(color);
+        for band in ys.windows(2) {
+            let (bottom, top) = (band[0], band[1]);
+            let middle = (bottom + top) / 2.0;
+            let mut crossing = edges
+                .iter()
+                .filter(|(a, b, _)| a[1] <= bottom && b[1] >= top)
+                .map(|edge| (x_at(edge, middle), edge))
+                .collect::<Vec<_>>();
+            crossing.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            let mut left = None;
+            for (_, edge) in crossing {
+                let was_inside = rule.is_inside(winding);
+                winding += edge.2;
+                match (was_inside, rule.is_inside(winding)) {
+                    (false, true) => left = Some(edge),
+                    (true, false) => {
+                        let left = left.take().unwrap();
+                        builder.quad([
+                            [x_at(left, bottom), bottom],
+                            [x_at(edge, bottom), bottom],
+                            [x_at(edge, top), top],
+                            [x_at(left, top), top],
+                        ]);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        builder.finish()
+    }
+
+    /// Tessellates the outline of the path with the given stroke width.
+    pub(crate) fn stroke<V: From<ShapeVertex>>(&self, width: f32, color: [f32; 3]) -> Geometry<V> {
+        let mut builder = Builder::new(color);
+        let half = width / 2.0;
+        for (points, closed) in self.polylines() {
+            let count = if closed {
+                points.len()
+            } else {
+                points.len() - 1
+            };
+            let segments = (0..count)
+                .map(|i| (points[i], points[(i + 1) % points.len()]))
+                .filter(|(a, b)| a != b)
+                .map(|(a, b)| {
+                    let direction = scale(sub(b, a), 1.0 / length(sub(b, a)));
+                    (a, b, scale([-direction[1], direction[0]], half))
+                })
+                .collect::<Vec<_>>();
+
+            for &(a, b, normal) in &segments {
+                builder.quad([
+                    add(a, normal),
+                    sub(a, normal),
+                    sub(b, normal),
+                    add(b, normal),
+                ]);
+            }
+
+            // Bevel both sides, one of them is covered by the segments anyway
+            let joins = segments.windows(2).map(|pair| (pair[0], pair[1]));
+            let closing =
+                (closed && segments.len() > 2).then(|| (*segments.last().unwrap(), segments[0]));
+            for ((_, corner, before), (_, _, after)) in joins.chain(closing) {
+                builder.triangle([corner, add(corner, before), add(corner, after)]);
+                builder.triangle([corner, sub(corner, after), sub(corner, before)]);
+            }
+        }
+        builder.finish()
+    }
+}
+
+/// The y coordinate at which the edges `a`-`b` and `c`-`d` cross, if they do
+/// between their end points.
+fn intersection_y(a: Point, b: Point, c: Point, d: Point) -> Option<f32> {
+    let (r, s) = (sub(b, a), sub(d, c));
+    let denominator = r[0] * s[1] - r[1] * s[0];
+    if denominator == 0.0 {
+        return None;
+    }
+    let ac = sub(c, a);
+    let t = (ac[0] * s[1] - ac[1] * s[0]) / denominator;
+    let u = (ac[0] * r[1] - ac[1] * r[0]) / denominator;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then(|| a[1] + t * r[1])
+}
+
+/// Appends the points of a segment flattened to lines, without its start.
+fn flatten(start: Point, segment: Segment, points: &mut Vec<Point>) {
+    // The segment count bounds the distance to the curve by `TOLERANCE`
+    let (count, point): (usize, Box<dyn Fn(f32) -> Point>) = match segment {
+        Segment::Line(end) => {
+            points.push(end);
+            return;
+        }
+        Segment::Quadratic(control, end) => {
+            let curvature = length(add(sub(start, scale(control, 2.0)), end));
+            let count = (curvature / (4.0 * TOLERANCE)).sqrt().ceil() as usize;
+            let point = move |t: f32| {
+                let s = 1.0 - t;
+                add(
+                    add(scale(start, s * s), scale(control, 2.0 * s * t)),
+                    scale(end, t * t),
+                )
+            };
+            (count, Box::new(point))
+        }
+        Segment::Cubic(c1, c2, end) => {
+            let curvature = length(add(sub(start, scale(c1, 2.0)), c2))
+                .max(length(add(sub(c1, scale(c2, 2.0)), end)));
+            let count = (3.0 * curvature / (4.0 * TOLERANCE)).sqrt().ceil() as usize;
+            let point = move |t: f32| {
+                let s = 1.0 - t;
+                add(
+                    add(scale(start, s * s * s), scale(c1, 3.0 * s * s * t)),
+                    add(scale(c2, 3.0 * s * t * t), scale(end, t * t * t)),
+                )
+            };
+            (count, Box::new(point))
+        }
+    };
+    let count = count.clamp(1, MAX_CURVE_SEGMENTS);
+    points.extend((1..=count).map(|i| point(i as f32 / count as f32)));
+}
+
+/// Converts an SVG elliptical arc to cubic Béziers, following the endpoint to
+/// center conversion of the SVG specification.
+fn arc(
+    start: Point,
+    radii: Point,
+    rotation: f32,
+    large_arc: bool,
+    sweep: bool,
+    end: Point,
+) -> Vec<Segment> {
+    if start == end {
+        return Vec::new();
+    }
+    let [mut rx, mut ry] = radii;
+    if rx == 0.0 || ry == 0.0 {
+        return vec![Segment::Line(end)];
+    }
+
+    let (sin, cos) = rotation.sin_cos();
+    let rotate = |p: Point| [cos * p[0] - sin * p[1], sin * p[0] + cos * p[1]];
+    let half = scale(sub(start, end), 0.5);
+    let [x, y] = [
+        cos * half[0] + sin * half[1],
+        -sin * half[0] + cos * half[1],
+    ];
+
+    // Scale up radii too small to reach the end
+    let lambda = (x / rx).powi(2) + (y / ry).powi(2);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = (rx * ry).powi(2) - (rx * y).powi(2) - (ry * x).powi(2);
+    let denominator = (rx * y).powi(2) + (ry * x).powi(2);
+    let mut factor = (numerator / denominator).max(0.0).sqrt();
+    if large_arc == sweep {
+        factor = -factor;
+    }
+    let center_prime = [factor * rx * y / ry, -factor * ry * x / rx];
+    let center = add(rotate(center_prime), scale(add(start, end), 0.5));
+
+    let angle = |v: Point| v[1].atan2(v[0]);
+    let start_angle = angle([(x - center_prime[0]) / rx, (y - center_prime[1]) / ry]);
+    let end_angle = angle([(-x - center_prime[0]) / rx, (-y - center_prime[1]) / ry]);
+    let mut delta = (end_angle - start_angle).rem_euclid(TAU);
+    if !sweep && delta > 0.0 {
+        delta -= TAU;
+    }
+
+    let on_ellipse = |t: f32| add(center, rotate([rx * t.cos(), ry * t.sin()]));
+    let tangent = |t: f32| rotate([-rx * t.sin(), ry * t.cos()]);
+    let count = (delta.abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+    let step = delta / count as f32;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    (0..count)
+        .map(|i| {
+            let t0 = start_angle + i as f32 * step;
+            let t1 = t0 + step;
+            let p0 = on_ellipse(t0);
+            // End exactly at the given point, without rounding errors
+            let p1 = if i + 1 == count { end } else { on_ellipse(t1) };
+            Segment::Cubic(
+                add(p0, scale(tangent(t0), k)),
+                sub(p1, scale(tangent(t1), k)),
+                p1,
+            )
+        })
+        .collect()
+}
+
+/// Collects triangles with shared vertices.
+struct Builder {
+    color: [f32; 3],
+    positions: Vec<Point>,
+    lookup: HashMap<[u32; 2], u32>,
+    indices: Vec<u32>,
+}
+
+impl Builder {
+    fn new(color: [f32; 3]) -> Self {
+        Self {
+            color,
+            positions: Vec::new(),
+            lookup: HashMap::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn vertex(&mut self, position: Point) -> u32 {
+        *self
+            .lookup
+            .entry(position.map(f32::to_bits))
+            .or_insert_with(|| {
+                self.positions.push(position);
+                self.positions.len() as u32 - 1
+            })
+    }
+
+    /// Adds a triangle, unless it's degenerate.
+    fn triangle(&mut self, corners: [Point; 3]) {
+        let [a, b, c] = corners;
+        let area = (b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1]);
+        if area.abs() > f32::EPSILON * TOLERANCE {
+            let indices = corners.map(|corner| self.vertex(corner));
+            self.indices.extend(indices);
+        }
+    }
+
+    /// Adds a quadrilateral given counter-clockwise.
+    fn quad(&mut self, [a, b, c, d]: [Point; 4]) {
+        self.triangle([a, b, c]);
+        self.triangle([a, c, d]);
+    }
+
+    fn finish<V: From<ShapeVertex>>(self) -> Geometry<V> {
+        let color = self.color;
+        Geometry {
+            vertices: self
+                .positions
+                .into_iter()
+                .map(|[x, y]| {
+                    V::from(ShapeVertex {
+                        position: [x, y, 0.0],
+                        color,
+                    })
+                })
+                .collect(),
+            indices: self.indices,
+            topology: PrimitiveTopology::TriangleList,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The area covered by a triangle list.
+    fn area(geometry: &Geometry<ShapeVertex>) -> f32 {
+        geometry
+            .indices
+            .chunks(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| geometry.vertices[triangle[i] as usize].position);
+                ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn parses_all_commands() {
+        let path = SvgPath::parse(
+            "M1,2 l1-1.5.5.5 H0 v-1 Z m1 1 3 4 Q1 1 2 2 T3 3 C4 4 5 5 6 6 S8 8 9 9 a1 1 0 0 0 2 0",
+        )
+        .unwrap();
+        let subpaths = &path.subpaths;
+        assert_eq!(subpaths.len(), 2);
+        assert_eq!(
+            subpaths[0].segments,
+            [
+                Segment::Line([2.0, 0.5]),
+                Segment::Line([2.5, 1.0]),
+                Segment::Line([0.0, 1.0]),
+                Segment::Line([0.0, 0.0]),
+            ]
+        );
+        assert!(subpaths[0].closed);
+
+        // The move after the close is relative to the start of the subpath
+        let second = &subpaths[1];
+        assert_eq!(second.start, [2.0, 3.0]);
+        assert_eq!(second.segments[0], Segment::Line([5.0, 7.0]));
+        assert_eq!(
+            second.segments[2],
+            Segment::Quadratic([3.0, 3.0], [3.0, 3.0])
+        );
+        assert_eq!(
+            second.segments[4],
+            Segment::Cubic([7.0, 7.0], [8.0, 8.0], [9.0, 9.0])
+        );
+        assert_eq!(second.segments.last().unwrap().end(), [11.0, 9.0]);
+
+        assert_eq!(
+            SvgPath::parse("L1 1").unwrap_err(),
+            "path data must start with a move at byte 1"
+        );
+        assert_eq!(
+            SvgPath::parse("M0 0 X").unwrap_err(),
+            "unknown command `X` at byte 5"
+        );
+        assert!(SvgPath::parse("M0 0 L1").is_err());
+        assert!(SvgPath::parse("M0 0 L1 1 Z 2").is_err());
+    }
+
+    #[test]
+    fn arcs_stay_on_the_circle() {
+        // A half circle of radius 1 around the origin, counter-clockwise
+        let segments = arc([1.0, 0.0], [1.0, 1.0], 0.0, false, true, [-1.0, 0.0]);
+        assert_eq!(segments.len(), 2);
+        let mut points = vec![[1.0, 0.0]];
+        for segment in segments {
+            flatten(*points.last().unwrap(), segment, &mut points);
+        }
+        for point in &points {
+            assert!((length(*point) - 1.0).abs() < 1e-3, "{point:?}");
+            assert!(point[1] >= -1e-6, "{point:?}");
+        }
+    }
+
+    #[test]
+    fn fills_with_holes() {
+        let square = "M0 0 H4 V4 H0 Z";
+        let inner_same = "M1 1 H3 V3 H1 Z";
+        let inner_reverse = "M1 1 V3 H3 V1 Z";
+        let fill = |data: String, rule| {
+            let geometry = SvgPath::parse(&data)
+                .unwrap()
+                .in_view_box([0.0, 0.0, 4.0, 4.0])
+                .fill::<ShapeVertex>(rule, [1.0; 3]);
+            assert_eq!(geometry.validate(), Ok(()));
+            area(&geometry)
+        };
+
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        assert!(close(fill(square.to_owned(), FillRule::NonZero), 1.0));
+        assert!(close(
+            fill(format!("{square} {inner_same}"), FillRule::NonZero),
+            1.0
+        ));
+        assert!(close(
+            fill(format!("{square} {inner_same}"), FillRule::EvenOdd),
+            0.75
+        ));
+        assert!(close(
+            fill(format!("{square} {inner_reverse}"), FillRule::NonZero),
+            0.75
+        ));
+
+        // The two triangles of a bow tie
+        assert!(close(
+            fill("M0 0 L4 4 V0 L0 4 Z".to_owned(), FillRule::NonZero),
+            0.5
+        ));
+    }
+
+    #[test]
+    fn strokes_cover_the_outline() {
+        let path = SvgPath::parse("M0 0 H4 V4 H0 Z")
+            .unwrap()
+            .in_view_box([0.0, 0.0, 4.0, 4.0]);
+        let stroke = path.stroke::<ShapeVertex>(0.1, [1.0; 3]);
+        assert_eq!(stroke.validate(), Ok(()));
+        // Four segments and both bevels at each corner
+        let expected = 4.0 * 0.1 + 8.0 * 0.05 * 0.05 / 2.0;
+        assert!((area(&stroke) - expected).abs() < 1e-5, "{}", area(&stroke));
+    }
+}
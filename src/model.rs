@@ -0,0 +1,104 @@
+//! Scenario drawing a mesh loaded from the file given by `--mesh`.
+//!
+//! Reproducing a user-reported issue often needs the exact asset that
+//! triggered it. The mesh is scaled to fit the center of the view and drawn
+//! with the colored polygon pipeline, see [`Mesh::load`] for the formats.
+
+use std::path::Path;
+
+use crate::mesh::{Geometry, Mesh, TopologyError};
+use crate::polygon::{PolygonDesc, PolygonInstance, PolygonRenderer};
+use crate::scenario::{Scenario, ScenarioEntry};
+use crate::with_color::{self, PolygonVertex};
+use crate::RenderContext;
+
+pub(crate) const NAME: &str = "mesh";
+pub(crate) const DESCRIPTION: &str = "Mesh loaded from the file given by `--mesh`.";
+
+/// Scale of the mesh after fitting it into the unit cube.
+const SCALE: f32 = 0.9;
+
+/// Loads the mesh at `path` into a registry entry.
+///
+/// Degenerate primitives are only warned about: exported assets often
+/// contain them, the GPU draws them without complaint, and they may well be
+/// part of what triggers the issue.
+pub(crate) fn entry(path: &Path) -> Result<ScenarioEntry, String> {
+    let geometry = Mesh::load(path)?
+        .fit_unit_cube()
+        .geometry::<PolygonVertex>();
+    match geometry.validate() {
+        Ok(()) => {}
+        Err(e @ TopologyError::DegeneratePrimitive { .. }) => {
+            log::warn!("Mesh `{}`: {e}", path.display());
+        }
+        Err(e) => return Err(format!("Invalid mesh `{}`: {e}", path.display())),
+    }
+    Ok(ScenarioEntry::new(NAME, DESCRIPTION, move |context| {
+        renderer(context, geometry.clone())
+    }))
+}
+
+fn renderer(context: &RenderContext, geometry: Geometry<PolygonVertex>) -> Box<dyn Scenario> {
+    Box::new(PolygonRenderer::from_desc(
+        context,
+        PolygonDesc {
+            name: NAME,
+            description: DESCRIPTION,
+            shader: with_color::SHADER,
+            geometry,
+            instances: vec![PolygonInstance::new([0.0, 0.0], SCALE)],
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalSize;
+
+    #[test]
+    fn draws_meshes_with_32_bit_indices() {
+        let Some(context) = RenderContext::test_context(PhysicalSize::new(16, 16)) else {
+            return;
+        };
+
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets");
+        let mut scenarios = vec![entry(&assets.join("cube.obj")).unwrap().build(&context)];
+
+        // A grid of points with more vertices than `Uint16` indices address
+        let side = 300;
+        let positions = (0..side * side)
+            .map(|i| {
+                [
+                    (i % side) as f32 / side as f32,
+                    (i / side) as f32 / side as f32,
+                    0.0,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let mesh = Mesh {
+            indices: (0..positions.len() as u32).collect(),
+            positions,
+            colors: None,
+            normals: None,
+            uvs: None,
+            topology: wgpu::PrimitiveTopology::PointList,
+        };
+        let geometry = mesh.fit_unit_cube().geometry::<PolygonVertex>();
+        assert_eq!(geometry.index_format(), wgpu::IndexFormat::Uint32);
+        scenarios.push(renderer(&context, geometry));
+
+        context.capture_frame(&scenarios).unwrap();
+        assert!(context.errors.is_empty(), "{}", context.errors.summary());
+    }
+
+    #[test]
+    fn keeps_degenerate_triangles() {
+        let path = std::env::temp_dir().join(format!("degenerate-{}.obj", std::process::id()));
+        std::fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nf 1 2 2\n").unwrap();
+        let entry = entry(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(entry.unwrap().name, NAME);
+    }
+}
@@ -0,0 +1,213 @@
+//! Command-line (native) and URL-query (web) options.
+//!
+//! On native, options are given as `--key value` or `--key=value`, e.g.
+//! `--scenario only_pos,with_color`. On the web, the same keys are read from
+//! the page URL query, e.g. `index.html?scenario=only_pos`.
+
+use log::warn;
+
+/// Options of a single run.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Options {
+    /// Names of the scenarios to render, `None` renders the default ones (see
+    /// [`crate::scenario::select`]) or sweeps all registered ones.
+    pub(crate) scenarios: Option<Vec<String>>,
+    /// Path of an OBJ or glTF file to draw, see [`crate::model`]. Without
+    /// `--scenario`, only the mesh is drawn.
+    pub(crate) mesh: Option<String>,
+    /// Print the available scenarios and exit.
+    pub(crate) list_scenarios: bool,
+    /// Renumber vertex attribute locations to be dense, see [`crate::compact`].
+    pub(crate) compact_locations: bool,
+    /// Load the shaders from disk and reload them on changes, see
+    /// [`crate::hot_reload`].
+    pub(crate) hot_reload: bool,
+    /// Render a single frame offscreen without a window and exit.
+    pub(crate) headless: bool,
+    /// Run the scenarios on every adapter and print the outcomes, see
+    /// [`crate::sweep`].
+    pub(crate) sweep: bool,
+    /// Path to save the headless frame to as PNG.
+    pub(crate) output: Option<String>,
+    /// Path to write the JSON report to, see [`crate::report`].
+    pub(crate) report: Option<String>,
+    /// Backends to use, see [`crate::config`].
+    pub(crate) backend: Option<String>,
+    /// Power preference of the adapter, see [`crate::config`].
+    pub(crate) power_preference: Option<String>,
+    /// Device features and limits profile, see [`crate::config`].
+    pub(crate) profile: Option<String>,
+    /// Whether to prefer, avoid or forbid sRGB surface formats, see
+    /// [`crate::surface`].
+    pub(crate) srgb: Option<String>,
+    /// Surface format to use if supported, e.g. `Bgra8Unorm`.
+    pub(crate) surface_format: Option<String>,
+    /// Present mode to use if supported, e.g. `Mailbox`.
+    pub(crate) present_mode: Option<String>,
+    /// Force the software fallback adapter.
+    pub(crate) fallback_adapter: bool,
+    /// Substring of the adapter name, see [`crate::config`].
+    pub(crate) adapter_name: Option<String>,
+}
+
+impl Options {
+    /// Reads the options from the command-line arguments.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn from_env() -> Result<Self, String> {
+        let mut args = std::env::args().skip(1);
+        let mut pairs = Vec::new();
+
+        while let Some(arg) = args.next() {
+            let Some(key) = arg.strip_prefix("--") else {
+                return Err(format!("Unexpected argument `{arg}`"));
+            };
+            if let Some((key, value)) = key.split_once('=') {
+                pairs.push((key.to_owned(), Some(value.to_owned())));
+            } else if Self::is_flag(key) {
+                pairs.push((key.to_owned(), None));
+            } else {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Missing value for `--{key}`"))?;
+                pairs.push((key.to_owned(), Some(value)));
+            }
+        }
+
+        Self::parse(pairs)
+    }
+
+    /// Reads the options from the query string of the page URL.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn from_env() -> Result<Self, String> {
+        let search = web_sys::window()
+            .ok_or("no global `window` exists")?
+            .location()
+            .search()
+            .map_err(|e| format!("Failed to read the URL query: {e:?}"))?;
+
+        let pairs = search
+            .trim_start_matches('?')
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) => (percent_decode(key), Some(percent_decode(value))),
+                None => (percent_decode(pair), None),
+            })
+            .collect::<Vec<_>>();
+
+        Self::parse(pairs)
+    }
+
+    /// Whether the given key takes no value.
+    fn is_flag(key: &str) -> bool {
+        matches!(
+            key,
+            "list-scenarios"
+                | "compact-locations"
+                | "hot-reload"
+                | "headless"
+                | "sweep"
+                | "fallback-adapter"
+        )
+    }
+
+    fn parse(pairs: impl IntoIterator<Item = (String, Option<String>)>) -> Result<Self, String> {
+        let mut options = Self::default();
+
+        for (key, value) in pairs {
+            match (key.as_str(), value) {
+                ("scenario", Some(value)) => {
+                    options.scenarios.get_or_insert_with(Vec::new).extend(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|name| !name.is_empty())
+                            .map(str::to_owned),
+                    );
+                }
+                ("mesh", Some(value)) => options.mesh = Some(value),
+                ("list-scenarios", None) => options.list_scenarios = true,
+                ("compact-locations", None) => options.compact_locations = true,
+                ("hot-reload", None) => options.hot_reload = true,
+                ("headless", None) => options.headless = true,
+                ("sweep", None) => options.sweep = true,
+                ("output", Some(value)) => options.output = Some(value),
+                ("report", Some(value)) => options.report = Some(value),
+                ("backend", Some(value)) => options.backend = Some(value),
+                ("power-preference", Some(value)) => options.power_preference = Some(value),
+                ("profile", Some(value)) => options.profile = Some(value),
+                ("srgb", Some(value)) => options.srgb = Some(value),
+                ("surface-format", Some(value)) => options.surface_format = Some(value),
+                ("present-mode", Some(value)) => options.present_mode = Some(value),
+                ("fallback-adapter", None) => options.fallback_adapter = true,
+                ("adapter-name", Some(value)) => options.adapter_name = Some(value),
+                (key, None) if !Self::is_flag(key) => {
+                    return Err(format!("Missing value for option `{key}`"));
+                }
+                (key, _) => {
+                    // Be lenient with unrelated query parameters on the web
+                    if cfg!(target_arch = "wasm32") {
+                        warn!("Ignoring unknown option `{key}`");
+                    } else {
+                        return Err(format!("Unknown option `--{key}`"));
+                    }
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Decodes `%XX` escapes and `+` of a URL query component.
+///
+/// Malformed escapes are kept literally, including a `+` in them.
+#[cfg(any(target_arch = "wasm32", test))]
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                // `from_str_radix` alone would accept a sign
+                let hex = Some(&bytes[i + 1..i + 3])
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| std::str::from_utf8(hex).ok());
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => {
+                        out.extend_from_slice(&bytes[i..i + 3]);
+                        i += 2;
+                    }
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_query_components() {
+        assert_eq!(
+            percent_decode("only_pos%2Cwith_color"),
+            "only_pos,with_color"
+        );
+        assert_eq!(percent_decode("a+b%20c"), "a b c");
+        assert_eq!(percent_decode("%C3%A9"), "\u{e9}");
+        // Malformed escapes are kept
+        assert_eq!(percent_decode("trailing%4"), "trailing%4");
+        assert_eq!(percent_decode("%+1"), "%+1");
+        assert_eq!(percent_decode("%zz%"), "%zz%");
+    }
+}
@@ -0,0 +1,532 @@
+//! Generated vertex-layout permutations.
+//!
+//! Instead of hand-writing one module per vertex layout, each
+//! [`LayoutPermutation`] describes its vertex buffers and attributes, from
+//! which a matching WGSL shader and the `VertexBufferLayout`s are generated.
+//! The shader reads every attribute, so none of them can be optimized away.
+//!
+//! Every permutation draws a pair of small quads (two instances) into its own
+//! cell of a grid in the lower left corner of the screen.
+
+use std::fmt::Write;
+
+use wgpu::util::DeviceExt;
+use wgpu::{VertexFormat, VertexStepMode};
+
+use crate::compact;
+use crate::reflect;
+use crate::scenario::{Requirements, Scenario, ScenarioEntry, VertexLayout};
+use crate::RenderContext;
+
+/// Number of vertices per drawn quad (as a triangle strip).
+const VERTEX_COUNT: u32 = 4;
+/// Number of quads drawn per permutation.
+const INSTANCE_COUNT: u32 = 2;
+/// Number of grid cells per row.
+const CELLS_PER_ROW: usize = 8;
+/// Size of a grid cell in clip space.
+const CELL_SIZE: f32 = 0.12;
+/// Size of a quad in clip space.
+const QUAD_SIZE: f32 = 0.05;
+
+/// A single vertex buffer of a permutation.
+#[derive(Debug, Clone)]
+pub(crate) struct BufferSpec {
+    pub(crate) step_mode: VertexStepMode,
+    /// Attributes as `(shader location, format)`, in buffer order.
+    pub(crate) attributes: Vec<(u32, VertexFormat)>,
+}
+
+/// A vertex layout to test, the buffer slots are given by the buffer order.
+#[derive(Debug, Clone)]
+pub(crate) struct LayoutPermutation {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) buffers: Vec<BufferSpec>,
+}
+
+impl LayoutPermutation {
+    pub(crate) fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            buffers: Vec::new(),
+        }
+    }
+
+    /// Adds the next vertex buffer slot.
+    pub(crate) fn buffer(
+        mut self,
+        step_mode: VertexStepMode,
+        attributes: impl IntoIterator<Item = (u32, VertexFormat)>,
+    ) -> Self {
+        self.buffers.push(BufferSpec {
+            step_mode,
+            attributes: attributes.into_iter().collect(),
+        });
+        self
+    }
+
+    /// The vertex limits needed by the layout, on top of the defaults.
+    pub(crate) fn requirements(&self) -> Requirements {
+        let mut requirements = Requirements::default();
+        let limits = &mut requirements.limits;
+
+        limits.max_vertex_buffers = self.buffers.len() as u32;
+        limits.max_vertex_attributes = self
+            .buffers
+            .iter()
+            .flat_map(|buffer| &buffer.attributes)
+            .map(|(location, _)| location + 1)
+            .max()
+            .unwrap_or(0);
+        limits.max_vertex_buffer_array_stride = self
+            .attributes()
+            .iter()
+            .map(|(_, stride)| *stride as u32)
+            .max()
+            .unwrap_or(0);
+
+        requirements
+    }
+
+    /// Returns the packed attributes and the array stride of each buffer.
+    pub(crate) fn attributes(&self) -> Vec<(Vec<wgpu::VertexAttribute>, wgpu::BufferAddress)> {
+        self.buffers
+            .iter()
+            .map(|buffer| {
+                let mut offset = 0;
+                let attributes = buffer
+                    .attributes
+                    .iter()
+                    .map(|&(shader_location, format)| {
+                        // Attribute offsets must be aligned to the component size
+                        offset = align_to(offset, format.size().min(4));
+                        let attribute = wgpu::VertexAttribute {
+                            format,
+                            offset,
+                            shader_location,
+                        };
+                        offset += format.size();
+                        attribute
+                    })
+                    .collect();
+                (attributes, align_to(offset, wgpu::VERTEX_STRIDE_ALIGNMENT))
+            })
+            .collect()
+    }
+
+    /// Generates the WGSL source drawing into the given grid cell.
+    ///
+    /// The source uses the color module, see [`crate::color`].
+    pub(crate) fn wgsl(&self, cell: usize) -> String {
+        let origin_x = -0.95 + (cell % CELLS_PER_ROW) as f32 * CELL_SIZE;
+        let origin_y = -0.95 + (cell / CELLS_PER_ROW) as f32 * CELL_SIZE;
+
+        let mut inputs = String::new();
+        let mut sum = String::new();
+        for (location, format) in self.buffers.iter().flat_map(|b| &b.attributes) {
+            let (ty, components) = wgsl_type(*format);
+            writeln!(inputs, "    @location({location}) a{location}: {ty},").unwrap();
+            if components == 1 {
+                writeln!(sum, "    sum = sum + f32(input.a{location});").unwrap();
+            } else {
+                for c in ["x", "y", "z", "w"].iter().take(components) {
+                    writeln!(sum, "    sum = sum + f32(input.a{location}.{c});").unwrap();
+                }
+            }
+        }
+
+        format!(
+            "\
+#include \"color.wgsl\"
+
+/// Vertex and instance input data
+struct VertexInput {{
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+{inputs}}};
+
+/// Output of the vertex shader and input of the fragment shader
+struct VertexOutput {{
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}};
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {{
+    var sum = 0.0;
+{sum}
+    let corner = vec2<f32>(f32(input.vertex_index & 1u), f32(input.vertex_index >> 1u));
+    let origin = vec2<f32>({origin_x:?} + f32(input.instance_index) * {instance_step:?}, {origin_y:?});
+
+    var output: VertexOutput;
+    output.clip_position = vec4<f32>(origin + corner * {QUAD_SIZE:?}, 0.0, 1.0);
+    output.color = vec4<f32>(0.25, 0.5 + 0.5 * fract(sum), 0.25, 1.0);
+    return output;
+}}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {{
+    return vec4<f32>(linear_to_target(srgb_to_linear(input.color.rgb)), input.color.a);
+}}
+",
+            instance_step = QUAD_SIZE * 1.1,
+        )
+    }
+}
+
+/// Returns the WGSL type and component count of a vertex format.
+fn wgsl_type(format: VertexFormat) -> (&'static str, usize) {
+    use VertexFormat::*;
+
+    match format {
+        Float32 => ("f32", 1),
+        Uint32 => ("u32", 1),
+        Sint32 => ("i32", 1),
+        Float32x2 | Unorm8x2 | Snorm8x2 | Unorm16x2 | Snorm16x2 | Float16x2 => ("vec2<f32>", 2),
+        Uint32x2 | Uint8x2 | Uint16x2 => ("vec2<u32>", 2),
+        Sint32x2 | Sint8x2 | Sint16x2 => ("vec2<i32>", 2),
+        Float32x3 => ("vec3<f32>", 3),
+        Uint32x3 => ("vec3<u32>", 3),
+        Sint32x3 => ("vec3<i32>", 3),
+        Float32x4 | Unorm8x4 | Snorm8x4 | Unorm16x4 | Snorm16x4 | Float16x4 => ("vec4<f32>", 4),
+        Uint32x4 | Uint8x4 | Uint16x4 => ("vec4<u32>", 4),
+        Sint32x4 | Sint8x4 | Sint16x4 => ("vec4<i32>", 4),
+        Float64 | Float64x2 | Float64x3 | Float64x4 => {
+            panic!("64-bit vertex formats are not supported by the generator")
+        }
+    }
+}
+
+fn align_to(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+/// Returns the list of permutations to sweep.
+pub(crate) fn permutations() -> Vec<LayoutPermutation> {
+    use VertexFormat::*;
+    use VertexStepMode::*;
+
+    let mut list = Vec::new();
+
+    // A position followed by a matrix, once dense and once with a gap at each
+    // location (`OnlyPos` skips location 1)
+    list.push(
+        LayoutPermutation::new("perm_dense", "Position at 0 and matrix at 1-4, no gap")
+            .buffer(Vertex, [(0, Float32x3)])
+            .buffer(Instance, (1..5).map(|l| (l, Float32x4))),
+    );
+    for gap in 0..5 {
+        let mut locations = (0..6).filter(|&l| l != gap);
+        let first = locations.next().unwrap();
+        list.push(
+            LayoutPermutation::new(
+                format!("perm_gap_{gap}"),
+                format!("Position and matrix at locations 0-5, skipping location {gap}"),
+            )
+            .buffer(Vertex, [(first, Float32x3)])
+            .buffer(Instance, locations.map(|l| (l, Float32x4))),
+        );
+    }
+
+    // Different formats next to the gap at location 1
+    for format in [
+        Float32, Float32x2, Float16x4, Unorm8x4, Snorm16x2, Uint32, Sint8x2,
+    ] {
+        let name = format!("{format:?}").to_lowercase();
+        list.push(
+            LayoutPermutation::new(
+                format!("perm_format_{name}"),
+                format!("Position at 0 and a {format:?} attribute at 2, skipping location 1"),
+            )
+            .buffer(Vertex, [(0, Float32x3), (2, format)]),
+        );
+    }
+
+    // Step modes
+    list.push(
+        LayoutPermutation::new(
+            "perm_vertex_only",
+            "All attributes per-vertex in one buffer, skipping location 1",
+        )
+        .buffer(Vertex, [(0, Float32x3), (2, Float32x4), (3, Float32x4)]),
+    );
+    list.push(
+        LayoutPermutation::new(
+            "perm_instance_only",
+            "All attributes per-instance in one buffer, skipping location 1",
+        )
+        .buffer(Instance, [(0, Float32x3), (2, Float32x4), (3, Float32x4)]),
+    );
+
+    // Buffer slot orders
+    list.push(
+        LayoutPermutation::new(
+            "perm_instance_first",
+            "Instance buffer in slot 0 and vertex buffer in slot 1, no gap",
+        )
+        .buffer(Instance, (1..5).map(|l| (l, Float32x4)))
+        .buffer(Vertex, [(0, Float32x3)]),
+    );
+    list.push(
+        LayoutPermutation::new(
+            "perm_instance_first_gap_1",
+            "Instance buffer in slot 0 and vertex buffer in slot 1, skipping location 1",
+        )
+        .buffer(Instance, (2..6).map(|l| (l, Float32x4)))
+        .buffer(Vertex, [(0, Float32x3)]),
+    );
+    list.push(
+        LayoutPermutation::new(
+            "perm_split_gap_1",
+            "Every attribute in its own buffer, skipping location 1",
+        )
+        .buffer(Vertex, [(0, Float32x3)])
+        .buffer(Instance, [(2, Float32x4)])
+        .buffer(Vertex, [(3, Float32x4)])
+        .buffer(Instance, [(4, Float32x4)]),
+    );
+
+    list
+}
+
+/// Registry entries of all permutations.
+pub(crate) fn entries() -> Vec<ScenarioEntry> {
+    permutations()
+        .into_iter()
+        .enumerate()
+        .map(|(cell, permutation)| {
+            ScenarioEntry::new(permutation.name.clone(), permutation.description.clone(), {
+                let permutation = permutation.clone();
+                move |context| {
+                    Box::new(PermutationScenario::from_permutation(
+                        context,
+                        permutation.clone(),
+                        cell,
+                    ))
+                }
+            })
+            .with_requirements(permutation.requirements())
+        })
+        .collect()
+}
+
+/// Renders a single generated layout permutation.
+pub(crate) struct PermutationScenario {
+    permutation: LayoutPermutation,
+    render_pipeline: wgpu::RenderPipeline,
+    vertex_layouts: Vec<VertexLayout>,
+    /// One buffer per vertex buffer slot.
+    vertex_buffers: Vec<wgpu::Buffer>,
+}
+
+impl PermutationScenario {
+    pub(crate) fn from_permutation(
+        context: &RenderContext,
+        permutation: LayoutPermutation,
+        cell: usize,
+    ) -> Self {
+        let source = context
+            .shader_source(&permutation.wgsl(cell))
+            .unwrap_or_else(|e| panic!("Invalid shader of `{}`: {e}", permutation.name));
+        log::debug!("Generated shader of {}:\n{source}", permutation.name);
+
+        // Optionally work around the attribute location gap.
+        let mut module = reflect::parse_wgsl(&source)
+            .unwrap_or_else(|e| panic!("Invalid shader of `{}`: {e}", permutation.name));
+        let (mut attributes, strides): (Vec<_>, Vec<_>) =
+            permutation.attributes().into_iter().unzip();
+        let source =
+            compact::compact_if_enabled(context, &source, &mut module, "vs_main", &mut attributes)
+                .unwrap_or_else(|e| panic!("Failed to compact `{}`: {e}", permutation.name));
+
+        let shader = context
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&permutation.name),
+                source: wgpu::ShaderSource::Wgsl(source),
+            });
+
+        let pipeline_layout =
+            context
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Permutation Pipeline Layout"),
+                    bind_group_layouts: &[],
+                    push_constant_ranges: &[],
+                });
+
+        let vertex_layouts = permutation
+            .buffers
+            .iter()
+            .zip(attributes.into_iter().zip(strides))
+            .map(|(buffer, (attributes, array_stride))| VertexLayout {
+                array_stride,
+                step_mode: buffer.step_mode,
+                attributes,
+            })
+            .collect::<Vec<_>>();
+        let buffer_layouts = vertex_layouts
+            .iter()
+            .map(VertexLayout::as_wgpu)
+            .collect::<Vec<_>>();
+
+        let render_pipeline =
+            context
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(&permutation.name),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &buffer_layouts,
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(context.swapchain_format.into())],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleStrip,
+                        ..Default::default()
+                    },
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                });
+
+        // Zeroed contents are enough, only the layout matters
+        let vertex_buffers = buffer_layouts
+            .iter()
+            .map(|layout| {
+                let count = match layout.step_mode {
+                    VertexStepMode::Vertex => VERTEX_COUNT,
+                    VertexStepMode::Instance => INSTANCE_COUNT,
+                };
+                let contents = vec![0; (layout.array_stride * count as u64) as usize];
+                context
+                    .device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Permutation Vertex Buffer"),
+                        contents: &contents,
+                        usage: wgpu::BufferUsages::VERTEX,
+                    })
+            })
+            .collect();
+
+        Self {
+            permutation,
+            render_pipeline,
+            vertex_layouts,
+            vertex_buffers,
+        }
+    }
+}
+
+impl Scenario for PermutationScenario {
+    fn name(&self) -> &str {
+        &self.permutation.name
+    }
+
+    fn description(&self) -> &str {
+        &self.permutation.description
+    }
+
+    fn vertex_layouts(&self) -> &[VertexLayout] {
+        &self.vertex_layouts
+    }
+
+    fn render<'a>(&'a self, pass: &mut wgpu::RenderBundleEncoder<'a>) {
+        pass.set_pipeline(&self.render_pipeline);
+
+        for (slot, buffer) in self.vertex_buffers.iter().enumerate() {
+            pass.set_vertex_buffer(slot as u32, buffer.slice(..));
+        }
+
+        pass.draw(0..VERTEX_COUNT, 0..INSTANCE_COUNT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::{self, Defines};
+
+    #[test]
+    fn generated_layouts_match_their_shaders() {
+        let mut names = Vec::new();
+        for (cell, permutation) in permutations().into_iter().enumerate() {
+            let name = &permutation.name;
+            assert!(!names.contains(name), "duplicate permutation `{name}`");
+            names.push(name.clone());
+
+            let source = shader::compose(&permutation.wgsl(cell), &Defines::new()).unwrap();
+            let module = reflect::parse_wgsl(&source).unwrap_or_else(|e| panic!("{name}: {e}"));
+
+            let attributes = permutation.attributes();
+            let layouts = permutation
+                .buffers
+                .iter()
+                .zip(&attributes)
+                .map(
+                    |(buffer, (attributes, array_stride))| wgpu::VertexBufferLayout {
+                        array_stride: *array_stride,
+                        step_mode: buffer.step_mode,
+                        attributes,
+                    },
+                )
+                .collect::<Vec<_>>();
+            reflect::check_vertex_layouts(&module, "vs_main", &layouts)
+                .unwrap_or_else(|e| panic!("{name}: {e}"));
+
+            // Attributes are packed in order, aligned to their component size
+            for (attributes, array_stride) in &attributes {
+                let mut end = 0;
+                for attribute in attributes {
+                    let size = attribute.format.size();
+                    assert_eq!(attribute.offset % size.min(4), 0, "{name}: {attribute:?}");
+                    assert!(attribute.offset >= end, "{name}: {attribute:?} overlaps");
+                    assert!(
+                        attribute.offset - end < 4,
+                        "{name}: {attribute:?} isn't packed"
+                    );
+                    end = attribute.offset + size;
+                }
+                assert_eq!(*array_stride, align_to(end, wgpu::VERTEX_STRIDE_ALIGNMENT));
+            }
+        }
+    }
+
+    #[test]
+    fn packs_attributes() {
+        let permutation = LayoutPermutation::new("test", "")
+            .buffer(
+                VertexStepMode::Vertex,
+                [(0, VertexFormat::Float32x3), (2, VertexFormat::Sint8x2)],
+            )
+            .buffer(
+                VertexStepMode::Instance,
+                [
+                    (3, VertexFormat::Unorm8x2),
+                    (4, VertexFormat::Float16x4),
+                    (5, VertexFormat::Float32),
+                ],
+            );
+        let layouts = permutation
+            .attributes()
+            .into_iter()
+            .map(|(attributes, stride)| {
+                let offsets = attributes.iter().map(|a| a.offset).collect::<Vec<_>>();
+                (offsets, stride)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(layouts, [(vec![0, 12], 16), (vec![0, 4, 12], 16)]);
+
+        let limits = permutation.requirements().limits;
+        assert_eq!(limits.max_vertex_buffers, 2);
+        assert_eq!(limits.max_vertex_attributes, 6);
+        assert_eq!(limits.max_vertex_buffer_array_stride, 16);
+    }
+}
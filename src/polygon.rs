@@ -0,0 +1,404 @@
+//! Instanced polygon rendering shared by the hexagon scenarios.
+//!
+//! A [`PolygonRenderer`] draws instances of a single indexed mesh with the
+//! view uniform at group 0. It's generic over the vertex and instance types,
+//! which describe their buffer layouts via [`VertexLayout`] and
+//! [`InstanceLayout`]. A scenario is then just a [`PolygonDesc`] with its
+//! shader, geometry and instances, e.g. `OnlyPos` and `WithColor`. The
+//! pipeline uses the topology of the geometry. The instances can be changed
+//! at any time through [`PolygonRenderer::instances_mut`] and are uploaded by
+//! [`Scenario::update`].
+
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use log::warn;
+use wgpu::util::DeviceExt;
+
+use crate::compact;
+use crate::errors::Phase;
+use crate::instance_buffer::InstanceBuffer;
+use crate::mesh::Geometry;
+use crate::reflect;
+use crate::scenario::{self, Scenario, ScenarioEntry};
+use crate::shader::{self, ShaderFile};
+use crate::webgl2;
+use crate::RenderContext;
+
+/// A vertex type of a [`PolygonRenderer`], in the vertex buffer at slot 0.
+pub(crate) trait VertexLayout: bytemuck::Pod {
+    /// The attributes of the vertex, at the locations of the shader inputs.
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// An instance type of a [`PolygonRenderer`], in the vertex buffer at slot 1.
+pub(crate) trait InstanceLayout: bytemuck::Pod {
+    /// The attributes of the instance, at the locations of the shader inputs.
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// GPU representation of a polygon instance.
+///
+/// Matches the `InstanceInput` of `shaders/instance.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PolygonInstance {
+    /// Transformation matrix.
+    pub(crate) transform: [[f32; 4]; 4],
+}
+impl PolygonInstance {
+    /// An instance scaled by `scale` and moved to `position`.
+    pub(crate) fn new(position: [f32; 2], scale: f32) -> Self {
+        Self {
+            transform: [
+                [scale, 0.0, 0.0, 0.0],
+                [0.0, scale, 0.0, 0.0],
+                [0.0, 0.0, scale, 0.0],
+                [position[0], position[1], 0.0, 1.0],
+            ],
+        }
+    }
+}
+impl InstanceLayout for PolygonInstance {
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute] =
+        &wgpu::vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4];
+}
+
+/// A square grid of `per_side` x `per_side` scaled instances, starting at
+/// `offset` and `displacement` apart.
+pub(crate) fn instance_grid(
+    per_side: usize,
+    displacement: f32,
+    scale: f32,
+    offset: f32,
+) -> Vec<PolygonInstance> {
+    (0..per_side)
+        .flat_map(|x| {
+            (0..per_side).map(move |y| {
+                let position = [
+                    x as f32 * displacement + offset,
+                    y as f32 * displacement + offset,
+                ];
+                PolygonInstance::new(position, scale)
+            })
+        })
+        .collect()
+}
+
+/// A single polygon of a row drawn by [`row_entries`], e.g. a topology or an
+/// icon given by `spec`.
+pub(crate) struct RowCase<T> {
+    pub(crate) name: &'static str,
+    pub(crate) description: &'static str,
+    pub(crate) spec: T,
+}
+
+/// Horizontal distance between the polygons of a row.
+const ROW_SPACING: f32 = 0.25;
+
+/// Registry entries drawing each case as one instance in a row at `row_y`,
+/// from left to right.
+pub(crate) fn row_entries<T: Sync, V: VertexLayout>(
+    cases: &'static [RowCase<T>],
+    shader: ShaderFile,
+    row_y: f32,
+    scale: f32,
+    geometry: fn(&RowCase<T>) -> Geometry<V>,
+) -> Vec<ScenarioEntry> {
+    cases
+        .iter()
+        .enumerate()
+        .map(|(i, case)| {
+            ScenarioEntry::new(case.name, case.description, move |context| {
+                let position = [-0.8 + i as f32 * ROW_SPACING, row_y];
+                Box::new(PolygonRenderer::from_desc(
+                    context,
+                    PolygonDesc {
+                        name: case.name,
+                        description: case.description,
+                        shader,
+                        geometry: geometry(case),
+                        instances: vec![PolygonInstance::new(position, scale)],
+                    },
+                ))
+            })
+        })
+        .collect()
+}
+
+/// What a [`PolygonRenderer`] draws.
+pub(crate) struct PolygonDesc<V, I> {
+    pub(crate) name: &'static str,
+    pub(crate) description: &'static str,
+    /// The shader with `vs_main` and `fs_main` entry points.
+    pub(crate) shader: ShaderFile,
+    pub(crate) geometry: Geometry<V>,
+    pub(crate) instances: Vec<I>,
+}
+
+/// Draws instances of an indexed mesh.
+pub(crate) struct PolygonRenderer<V, I> {
+    name: &'static str,
+    description: &'static str,
+    shader: ShaderFile,
+    topology: wgpu::PrimitiveTopology,
+    index_format: wgpu::IndexFormat,
+    pipeline: Pipeline,
+    /// The bind group of the view uniform.
+    view_bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+    instances: InstanceBuffer<I>,
+    _types: PhantomData<fn() -> (V, I)>,
+}
+
+/// The render pipeline and what it was created from.
+struct Pipeline {
+    render_pipeline: wgpu::RenderPipeline,
+    vertex_layouts: Vec<scenario::VertexLayout>,
+    /// The shader files loaded from disk, if any.
+    shader_files: Vec<PathBuf>,
+}
+
+impl<V: VertexLayout, I: InstanceLayout> PolygonRenderer<V, I> {
+    pub(crate) fn from_desc(context: &RenderContext, desc: PolygonDesc<V, I>) -> Self {
+        //
+        // Pipeline setup
+        //
+
+        let geometry = &desc.geometry;
+        geometry
+            .validate()
+            .unwrap_or_else(|e| panic!("Invalid geometry of `{}`: {e}", desc.name));
+
+        let index_format = geometry.index_format();
+        let pipeline = Self::create_pipeline(
+            context,
+            desc.name,
+            &desc.shader,
+            geometry.topology,
+            index_format,
+        )
+        .unwrap_or_else(|e| panic!("Failed to create the pipeline of `{}`: {e}", desc.name));
+
+        //
+        // Shape setup
+        //
+
+        let vertex_buffer = context
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Shapes Vertex Buffer"),
+                contents: bytemuck::cast_slice(&geometry.vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+
+        let index_buffer = context
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Shapes Index Buffer"),
+                contents: &geometry.index_bytes(),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+        let instances = InstanceBuffer::new(context, "Shape Instance Buffer", desc.instances);
+
+        Self {
+            name: desc.name,
+            description: desc.description,
+            shader: desc.shader,
+            topology: geometry.topology,
+            index_format,
+            pipeline,
+            view_bind_group: context.create_view_bind_group(),
+            vertex_buffer,
+            index_buffer,
+            index_count: geometry.indices.len() as u32,
+            instances,
+            _types: PhantomData,
+        }
+    }
+
+    /// The instances, uploaded before the next frame.
+    pub(crate) fn instances_mut(&mut self) -> &mut InstanceBuffer<I> {
+        &mut self.instances
+    }
+
+    /// Creates the render pipeline from the current shader source.
+    fn create_pipeline(
+        context: &RenderContext,
+        name: &str,
+        shader: &ShaderFile,
+        topology: wgpu::PrimitiveTopology,
+        index_format: wgpu::IndexFormat,
+    ) -> Result<Pipeline, String> {
+        let shader = context.load_shader(shader)?;
+        let shader_source = shader.source;
+
+        // Make sure the shader is valid and the vertex layouts match its
+        // inputs.
+        let mut module =
+            reflect::parse_wgsl(&shader_source).map_err(|e| format!("Invalid shader: {e}"))?;
+        shader::validate(&module, &shader_source)?;
+        let layouts = [V::desc(), I::desc()];
+        reflect::check_vertex_layouts(&module, "vs_main", &layouts)
+            .map_err(|e| format!("Invalid vertex layout: {e}"))?;
+
+        // Optionally work around the attribute location gap.
+        let mut attributes = layouts
+            .iter()
+            .map(|layout| layout.attributes.to_vec())
+            .collect::<Vec<_>>();
+        let source = compact::compact_if_enabled(
+            context,
+            &shader_source,
+            &mut module,
+            "vs_main",
+            &mut attributes,
+        )
+        .map_err(|e| format!("Failed to compact: {e}"))?;
+        let vertex_layouts = layouts
+            .iter()
+            .zip(attributes)
+            .map(|(layout, attributes)| scenario::VertexLayout {
+                array_stride: layout.array_stride,
+                step_mode: layout.step_mode,
+                attributes,
+            })
+            .collect::<Vec<_>>();
+        let buffers = vertex_layouts
+            .iter()
+            .map(scenario::VertexLayout::as_wgpu)
+            .collect::<Vec<_>>();
+
+        // Compile the shaders from source.
+        let shader_module = context
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Polygon Shader"),
+                source: wgpu::ShaderSource::Wgsl(source),
+            });
+
+        // Define the pipeline layout.
+        let pipeline_layout =
+            context
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Polygon Pipeline Layout"),
+                    bind_group_layouts: &[&context.view_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+        // Create the render pipeline.
+        let descriptor = wgpu::RenderPipelineDescriptor {
+            label: Some("Polygon Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(context.swapchain_format.into())],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology,
+                // Strips are restarted at the maximum index
+                strip_index_format: topology.is_strip().then_some(index_format),
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        };
+        if cfg!(debug_assertions) {
+            for hazard in webgl2::validate_for_webgl2(&descriptor, &module) {
+                warn!("WebGL2 hazard in `{name}`: {hazard}");
+            }
+        }
+        let render_pipeline = context.device.create_render_pipeline(&descriptor);
+
+        Ok(Pipeline {
+            render_pipeline,
+            vertex_layouts,
+            shader_files: shader.files,
+        })
+    }
+}
+
+impl<V: VertexLayout, I: InstanceLayout> Scenario for PolygonRenderer<V, I> {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn vertex_layouts(&self) -> &[scenario::VertexLayout] {
+        &self.pipeline.vertex_layouts
+    }
+
+    fn shader_files(&self) -> &[PathBuf] {
+        &self.pipeline.shader_files
+    }
+
+    fn reload_shaders(&mut self, context: &RenderContext) -> Result<(), String> {
+        let errors = context.errors.len();
+        let pipeline = context.capture_errors(Some(self.name), Phase::PipelineCreation, || {
+            Self::create_pipeline(
+                context,
+                self.name,
+                &self.shader,
+                self.topology,
+                self.index_format,
+            )
+        })?;
+        if context.errors.len() > errors {
+            return Err("wgpu rejected the pipeline, see the errors above".to_owned());
+        }
+        self.pipeline = pipeline;
+        Ok(())
+    }
+
+    fn update(&mut self, context: &RenderContext) {
+        self.instances.upload(context);
+    }
+
+    fn render<'a>(&'a self, pass: &mut wgpu::RenderBundleEncoder<'a>) {
+        if self.instances.is_empty() {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline.render_pipeline);
+        pass.set_bind_group(0, &self.view_bind_group, &[]);
+
+        // Set normal vertex buffer.
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        // Set per-instance vertex buffer.
+        pass.set_vertex_buffer(1, self.instances.slice());
+        // Set index buffer.
+        pass.set_index_buffer(self.index_buffer.slice(..), self.index_format);
+
+        // Draw the shapes.
+        pass.draw_indexed(0..self.index_count, 0, 0..self.instances.len() as u32);
+    }
+}
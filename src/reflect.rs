@@ -0,0 +1,322 @@
+//! Shader reflection via naga.
+//!
+//! The vertex buffer layouts of a pipeline are written by hand next to the
+//! Rust structs, while the `@location`s live in the WGSL source. This module
+//! reads the vertex inputs of an entry point and checks a set of
+//! `VertexBufferLayout`s against them, so a mismatch is reported at startup
+//! instead of as an obscure driver error on some backends.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use naga::{ScalarKind, TypeInner};
+
+/// A vertex input of a shader entry point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VertexInput {
+    pub(crate) location: u32,
+    pub(crate) kind: ScalarKind,
+    /// Number of components, 1 for scalars.
+    pub(crate) components: u32,
+}
+
+/// A mismatch between a shader and its vertex buffer layouts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LayoutError {
+    /// The shader source failed to parse.
+    Parse(String),
+    /// The entry point does not exist or is not a vertex shader.
+    MissingEntryPoint(String),
+    /// A vertex input has a type which can't be fed from a vertex buffer.
+    UnsupportedInput { location: u32, ty: String },
+    /// A vertex input has no attribute in any of the layouts.
+    MissingAttribute { location: u32 },
+    /// Two attributes use the same location.
+    DuplicateLocation { location: u32 },
+    /// The attribute format does not match the shader input type.
+    FormatMismatch {
+        location: u32,
+        format: wgpu::VertexFormat,
+        expected: (ScalarKind, u32),
+    },
+    /// The attribute does not fit into the array stride of its buffer.
+    OutOfStride {
+        location: u32,
+        end: wgpu::BufferAddress,
+        array_stride: wgpu::BufferAddress,
+    },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(msg) => write!(f, "failed to parse shader: {msg}"),
+            Self::MissingEntryPoint(name) => write!(f, "no vertex entry point `{name}`"),
+            Self::UnsupportedInput { location, ty } => {
+                write!(
+                    f,
+                    "vertex input at location {location} has unsupported type {ty}"
+                )
+            }
+            Self::MissingAttribute { location } => {
+                write!(
+                    f,
+                    "shader reads location {location}, but no vertex buffer provides it"
+                )
+            }
+            Self::DuplicateLocation { location } => {
+                write!(
+                    f,
+                    "location {location} is provided by more than one attribute"
+                )
+            }
+            Self::FormatMismatch {
+                location,
+                format,
+                expected: (kind, components),
+            } => write!(
+                f,
+                "attribute at location {location} has format {format:?}, \
+                but the shader expects {components} component(s) of {kind:?}"
+            ),
+            Self::OutOfStride {
+                location,
+                end,
+                array_stride,
+            } => write!(
+                f,
+                "attribute at location {location} ends at byte {end}, \
+                beyond the array stride of {array_stride}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// Parses a WGSL shader into a naga module.
+pub(crate) fn parse_wgsl(source: &str) -> Result<naga::Module, LayoutError> {
+    naga::front::wgsl::parse_str(source)
+        .map_err(|err| LayoutError::Parse(err.emit_to_string(source)))
+}
+
+/// Returns the vertex inputs of the given vertex entry point, by location.
+pub(crate) fn vertex_inputs(
+    module: &naga::Module,
+    entry_point: &str,
+) -> Result<Vec<VertexInput>, LayoutError> {
+    let entry_point = module
+        .entry_points
+        .iter()
+        .find(|ep| ep.name == entry_point && ep.stage == naga::ShaderStage::Vertex)
+        .ok_or_else(|| LayoutError::MissingEntryPoint(entry_point.to_owned()))?;
+
+    // Flatten struct arguments into their members
+    let mut bindings = Vec::new();
+    for arg in &entry_point.function.arguments {
+        match (&arg.binding, &module.types[arg.ty].inner) {
+            (Some(binding), _) => bindings.push((binding, arg.ty)),
+            (None, TypeInner::Struct { members, .. }) => {
+                for member in members {
+                    if let Some(binding) = &member.binding {
+                        bindings.push((binding, member.ty));
+                    }
+                }
+            }
+            (None, _) => {}
+        }
+    }
+
+    let mut inputs = bindings
+        .into_iter()
+        .filter_map(|(binding, ty)| match *binding {
+            naga::Binding::Location { location, .. } => Some((location, ty)),
+            naga::Binding::BuiltIn(_) => None,
+        })
+        .map(|(location, ty)| {
+            let (kind, components) = match module.types[ty].inner {
+                TypeInner::Scalar { kind, .. } => (kind, 1),
+                TypeInner::Vector { size, kind, .. } => (kind, size as u32),
+                ref other => {
+                    return Err(LayoutError::UnsupportedInput {
+                        location,
+                        ty: format!("{other:?}"),
+                    })
+                }
+            };
+            Ok(VertexInput {
+                location,
+                kind,
+                components,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    inputs.sort_by_key(|input| input.location);
+
+    Ok(inputs)
+}
+
+/// Returns the scalar kind and component count a vertex format provides.
+pub(crate) fn format_shader_type(format: wgpu::VertexFormat) -> (ScalarKind, u32) {
+    use wgpu::VertexFormat::*;
+
+    let kind = match format {
+        Uint8x2 | Uint8x4 | Uint16x2 | Uint16x4 | Uint32 | Uint32x2 | Uint32x3 | Uint32x4 => {
+            ScalarKind::Uint
+        }
+        Sint8x2 | Sint8x4 | Sint16x2 | Sint16x4 | Sint32 | Sint32x2 | Sint32x3 | Sint32x4 => {
+            ScalarKind::Sint
+        }
+        _ => ScalarKind::Float,
+    };
+    let components = match format {
+        Float32 | Uint32 | Sint32 | Float64 => 1,
+        Uint8x2 | Sint8x2 | Unorm8x2 | Snorm8x2 | Uint16x2 | Sint16x2 | Unorm16x2 | Snorm16x2
+        | Float16x2 | Float32x2 | Uint32x2 | Sint32x2 | Float64x2 => 2,
+        Float32x3 | Uint32x3 | Sint32x3 | Float64x3 => 3,
+        _ => 4,
+    };
+    (kind, components)
+}
+
+/// Checks the vertex buffer layouts against the inputs of a vertex entry point.
+pub(crate) fn check_vertex_layouts(
+    module: &naga::Module,
+    entry_point: &str,
+    layouts: &[wgpu::VertexBufferLayout],
+) -> Result<(), LayoutError> {
+    let inputs = vertex_inputs(module, entry_point)?;
+
+    let mut attributes = BTreeMap::new();
+    for layout in layouts {
+        for attr in layout.attributes {
+            let end = attr.offset + attr.format.size();
+            if end > layout.array_stride {
+                return Err(LayoutError::OutOfStride {
+                    location: attr.shader_location,
+                    end,
+                    array_stride: layout.array_stride,
+                });
+            }
+            if attributes.insert(attr.shader_location, attr).is_some() {
+                return Err(LayoutError::DuplicateLocation {
+                    location: attr.shader_location,
+                });
+            }
+        }
+    }
+
+    for input in inputs {
+        let attr = attributes
+            .get(&input.location)
+            .ok_or(LayoutError::MissingAttribute {
+                location: input.location,
+            })?;
+        let expected = (input.kind, input.components);
+        if format_shader_type(attr.format) != expected {
+            return Err(LayoutError::FormatMismatch {
+                location: input.location,
+                format: attr.format,
+                expected,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = "
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(2) index: u32,
+};
+
+@vertex
+fn vs_main(input: VertexInput) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(input.position, f32(input.index));
+}
+";
+
+    /// Checks the attributes as a single vertex buffer with the given stride.
+    fn check(array_stride: u64, attributes: &[wgpu::VertexAttribute]) -> Result<(), LayoutError> {
+        let module = parse_wgsl(SHADER).unwrap();
+        let layout = wgpu::VertexBufferLayout {
+            array_stride,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes,
+        };
+        check_vertex_layouts(&module, "vs_main", &[layout])
+    }
+
+    #[test]
+    fn accepts_matching_layouts() {
+        check(16, &wgpu::vertex_attr_array![0 => Float32x3, 2 => Uint32]).unwrap();
+        // Unused attributes are fine
+        check(
+            20,
+            &wgpu::vertex_attr_array![0 => Float32x3, 2 => Uint32, 1 => Float32],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reports_missing_attributes() {
+        assert_eq!(
+            check(12, &wgpu::vertex_attr_array![0 => Float32x3]),
+            Err(LayoutError::MissingAttribute { location: 2 })
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_locations() {
+        assert_eq!(
+            check(16, &wgpu::vertex_attr_array![0 => Float32x3, 0 => Uint32]),
+            Err(LayoutError::DuplicateLocation { location: 0 })
+        );
+    }
+
+    #[test]
+    fn reports_format_mismatches() {
+        assert_eq!(
+            check(16, &wgpu::vertex_attr_array![0 => Float32x3, 2 => Float32]),
+            Err(LayoutError::FormatMismatch {
+                location: 2,
+                format: wgpu::VertexFormat::Float32,
+                expected: (ScalarKind::Uint, 1),
+            })
+        );
+        assert_eq!(
+            check(16, &wgpu::vertex_attr_array![0 => Float32x2, 2 => Uint32]),
+            Err(LayoutError::FormatMismatch {
+                location: 0,
+                format: wgpu::VertexFormat::Float32x2,
+                expected: (ScalarKind::Float, 3),
+            })
+        );
+    }
+
+    #[test]
+    fn reports_attributes_beyond_the_stride() {
+        assert_eq!(
+            check(12, &wgpu::vertex_attr_array![0 => Float32x3, 2 => Uint32]),
+            Err(LayoutError::OutOfStride {
+                location: 2,
+                end: 16,
+                array_stride: 12,
+            })
+        );
+    }
+
+    #[test]
+    fn reports_missing_entry_points() {
+        let module = parse_wgsl(SHADER).unwrap();
+        assert_eq!(
+            check_vertex_layouts(&module, "fs_main", &[]),
+            Err(LayoutError::MissingEntryPoint("fs_main".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Machine-readable JSON report of a run, for attaching to bug reports.
+//!
+//! The report contains the adapter and its downlevel capabilities, the device
+//! profile, the surface configuration, the vertex layouts of each scenario and
+//! the captured errors. It's written with `--report report.json` on native and
+//! logged to the console on the web.
+//!
+//! Errors while rendering a frame have no scenario, as they concern the shared
+//! render bundle of all scenarios (see [`crate::errors`]).
+
+use std::fmt;
+
+use crate::errors::CapturedError;
+use crate::json::Json;
+use crate::scenario::{Scenario, VertexLayout};
+use crate::RenderContext;
+
+/// Builds the report of the context and its scenarios.
+pub(crate) fn report(context: &RenderContext, scenarios: &[Box<dyn Scenario>]) -> Json {
+    let info = &context.adapter_info;
+    let size = context.size();
+
+    Json::object([
+        (
+            "adapter",
+            Json::object([
+                ("name", Json::String(info.name.clone())),
+                ("vendor", Json::Number(info.vendor as f64)),
+                ("device", Json::Number(info.device as f64)),
+                ("device_type", Json::debug(info.device_type)),
+                ("driver", Json::String(info.driver.clone())),
+                ("driver_info", Json::String(info.driver_info.clone())),
+                ("backend", Json::debug(info.backend)),
+            ]),
+        ),
+        ("backend", Json::debug(info.backend)),
+        (
+            "downlevel",
+            Json::object([
+                ("flags", flag_names(context.downlevel.flags)),
+                ("shader_model", Json::debug(context.downlevel.shader_model)),
+                (
+                    "is_webgpu_compliant",
+                    Json::Bool(context.downlevel.is_webgpu_compliant()),
+                ),
+            ]),
+        ),
+        (
+            "device",
+            Json::object([
+                ("profile", Json::String(context.profile.name().to_owned())),
+                ("features", flag_names(context.device.features())),
+            ]),
+        ),
+        ("swapchain_format", Json::debug(context.swapchain_format)),
+        (
+            "present_mode",
+            context.present_mode().map_or(Json::Null, Json::debug),
+        ),
+        (
+            "size",
+            Json::object([
+                ("width", Json::Number(size.width as f64)),
+                ("height", Json::Number(size.height as f64)),
+            ]),
+        ),
+        ("compact_locations", Json::Bool(context.compact_locations)),
+        (
+            "scenarios",
+            Json::Array(scenarios.iter().map(|s| scenario(s.as_ref())).collect()),
+        ),
+        (
+            "errors",
+            Json::Array(context.errors.errors().iter().map(error).collect()),
+        ),
+    ])
+}
+
+/// Lists the names of the set flags.
+fn flag_names(flags: impl fmt::Debug) -> Json {
+    // `bitflags` formats the set flags as `A | B`
+    Json::Array(
+        format!("{flags:?}")
+            .split(" | ")
+            .filter(|name| !name.is_empty() && *name != "(empty)")
+            .map(|name| Json::String(name.to_owned()))
+            .collect(),
+    )
+}
+
+fn scenario(scenario: &dyn Scenario) -> Json {
+    Json::object([
+        ("name", Json::String(scenario.name().to_owned())),
+        (
+            "description",
+            Json::String(scenario.description().to_owned()),
+        ),
+        (
+            "vertex_layouts",
+            Json::Array(
+                scenario
+                    .vertex_layouts()
+                    .iter()
+                    .map(vertex_layout)
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+fn vertex_layout(layout: &VertexLayout) -> Json {
+    Json::object([
+        ("array_stride", Json::Number(layout.array_stride as f64)),
+        ("step_mode", Json::debug(layout.step_mode)),
+        (
+            "attributes",
+            Json::Array(
+                layout
+                    .attributes
+                    .iter()
+                    .map(|attr| {
+                        Json::object([
+                            ("location", Json::Number(attr.shader_location as f64)),
+                            ("format", Json::debug(attr.format)),
+                            ("offset", Json::Number(attr.offset as f64)),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+fn error(error: &CapturedError) -> Json {
+    Json::object([
+        (
+            "scenario",
+            error
+                .scenario
+                .as_ref()
+                .map_or(Json::Null, |s| Json::String(s.clone())),
+        ),
+        ("phase", Json::String(error.phase.to_string())),
+        ("message", Json::String(error.message.clone())),
+    ])
+}
+
+/// Writes the report to `path` natively, or logs it on the web.
+pub(crate) fn write_report(
+    context: &RenderContext,
+    scenarios: &[Box<dyn Scenario>],
+    path: &str,
+) -> Result<(), String> {
+    let report = report(context, scenarios).to_string();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::fs::write(path, report + "\n").map_err(|e| format!("Failed to write {path}: {e}"))?;
+        log::warn!("Saved report to {path}");
+    }
+    #[cfg(target_arch = "wasm32")]
+    log::warn!("Report {path}:\n{report}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario;
+    use winit::dpi::PhysicalSize;
+
+    #[test]
+    fn reports_scenarios() {
+        let Some(context) = RenderContext::test_context(PhysicalSize::new(16, 16)) else {
+            return;
+        };
+        let scenarios = scenario::select(Some(&["only_pos".to_owned()]))
+            .unwrap()
+            .iter()
+            .map(|entry| entry.build(&context))
+            .collect::<Vec<_>>();
+
+        let report = report(&context, &scenarios).to_string();
+        assert!(report.contains("\"swapchain_format\": \"Rgba8Unorm\""));
+        assert!(report.contains("\"present_mode\": null"));
+        assert!(report.contains("\"name\": \"only_pos\""));
+        // The instance buffer of `only_pos` starts at location 2
+        assert!(report.contains("\"location\": 2"));
+        assert!(report.contains("\"errors\": []"));
+    }
+}
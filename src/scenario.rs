@@ -0,0 +1,260 @@
+//! Reproduction scenarios and their registry.
+//!
+//! A scenario is a self-contained set of pipelines and buffers that renders
+//! into the shared render bundle each frame. New reproduction cases are added
+//! by implementing [`Scenario`] and listing them in [`registry`].
+
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::errors::Phase;
+use crate::icons;
+use crate::markers::TrainMarkers;
+use crate::only_pos::OnlyPos;
+use crate::permutation;
+use crate::shapes;
+use crate::topology;
+use crate::with_color::WithColor;
+use crate::RenderContext;
+
+/// A single reproduction case.
+pub(crate) trait Scenario {
+    /// Short, unique, machine-friendly name (e.g. `only_pos`).
+    fn name(&self) -> &str;
+
+    /// Human readable description of what this scenario exercises.
+    fn description(&self) -> &str;
+
+    /// The vertex buffer layouts of the pipeline, as actually used (i.e.
+    /// after the optional location compaction).
+    fn vertex_layouts(&self) -> &[VertexLayout];
+
+    /// The shader files the pipelines were loaded from, see
+    /// [`crate::hot_reload`].
+    fn shader_files(&self) -> &[PathBuf] {
+        &[]
+    }
+
+    /// Recreates the pipelines from the shader files.
+    ///
+    /// On error, the previous pipelines are kept.
+    fn reload_shaders(&mut self, context: &RenderContext) -> Result<(), String> {
+        let _ = context;
+        Ok(())
+    }
+
+    /// Prepares the next frame, e.g. uploads changed instances.
+    fn update(&mut self, context: &RenderContext) {
+        let _ = context;
+    }
+
+    /// Records the draw calls of this scenario.
+    fn render<'a>(&'a self, pass: &mut wgpu::RenderBundleEncoder<'a>);
+}
+
+/// An owned [`wgpu::VertexBufferLayout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VertexLayout {
+    pub(crate) array_stride: wgpu::BufferAddress,
+    pub(crate) step_mode: wgpu::VertexStepMode,
+    pub(crate) attributes: Vec<wgpu::VertexAttribute>,
+}
+impl VertexLayout {
+    pub(crate) fn as_wgpu(&self) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.array_stride,
+            step_mode: self.step_mode,
+            attributes: &self.attributes,
+        }
+    }
+}
+
+/// Constructor of a boxed scenario.
+type BuildFn = Rc<dyn Fn(&RenderContext) -> Box<dyn Scenario>>;
+
+/// A registered, not yet constructed scenario.
+#[derive(Clone)]
+pub(crate) struct ScenarioEntry {
+    /// Name of the scenario, must match [`Scenario::name`].
+    pub(crate) name: Cow<'static, str>,
+    /// Description of the scenario, must match [`Scenario::description`].
+    pub(crate) description: Cow<'static, str>,
+    /// What the device must support to build the scenario.
+    pub(crate) requirements: Requirements,
+    /// Whether the scenario is rendered when none are selected explicitly.
+    pub(crate) in_default: bool,
+    build: BuildFn,
+}
+impl ScenarioEntry {
+    pub(crate) fn new(
+        name: impl Into<Cow<'static, str>>,
+        description: impl Into<Cow<'static, str>>,
+        build: impl Fn(&RenderContext) -> Box<dyn Scenario> + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            requirements: Requirements::default(),
+            in_default: false,
+            build: Rc::new(build),
+        }
+    }
+
+    /// Renders the scenario when none are selected explicitly.
+    pub(crate) fn in_default(mut self) -> Self {
+        self.in_default = true;
+        self
+    }
+
+    pub(crate) fn with_requirements(mut self, requirements: Requirements) -> Self {
+        self.requirements = requirements;
+        self
+    }
+
+    /// Why the scenario can't be built on the device of the context, if at
+    /// all.
+    pub(crate) fn unsupported_reason(&self, context: &RenderContext) -> Option<String> {
+        self.requirements.unsupported_reason(&context.device)
+    }
+
+    /// Constructs the scenario, capturing its pipeline creation errors.
+    pub(crate) fn build(&self, context: &RenderContext) -> Box<dyn Scenario> {
+        let scenario = context.capture_errors(Some(&self.name), Phase::PipelineCreation, || {
+            (self.build)(context)
+        });
+        debug_assert_eq!(scenario.name(), self.name);
+        debug_assert_eq!(scenario.description(), self.description);
+        scenario
+    }
+}
+
+/// Features and limits a scenario needs from the device.
+#[derive(Debug, Clone)]
+pub(crate) struct Requirements {
+    pub(crate) features: wgpu::Features,
+    pub(crate) limits: wgpu::Limits,
+}
+
+impl Default for Requirements {
+    /// No features and the WebGL2 limits, which every profile provides.
+    fn default() -> Self {
+        Self {
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::downlevel_webgl2_defaults(),
+        }
+    }
+}
+
+impl Requirements {
+    /// Describes the missing features and exceeded limits of the device.
+    pub(crate) fn unsupported_reason(&self, device: &wgpu::Device) -> Option<String> {
+        let mut reasons = Vec::new();
+
+        let missing = self.features - device.features();
+        if !missing.is_empty() {
+            reasons.push(format!("missing features {missing:?}"));
+        }
+        self.limits.check_limits_with_fail_fn(
+            &device.limits(),
+            false,
+            |name, required, allowed| {
+                reasons.push(format!("{name} is {allowed}, but {required} is required"));
+            },
+        );
+
+        (!reasons.is_empty()).then(|| reasons.join(", "))
+    }
+}
+
+/// Returns all known scenarios, in render order.
+pub(crate) fn registry() -> Vec<ScenarioEntry> {
+    let mut entries = vec![
+        ScenarioEntry::new(OnlyPos::NAME, OnlyPos::DESCRIPTION, |context| {
+            Box::new(OnlyPos::new(context))
+        })
+        .in_default(),
+        ScenarioEntry::new(WithColor::NAME, WithColor::DESCRIPTION, |context| {
+            Box::new(WithColor::new(context))
+        })
+        .in_default(),
+    ];
+    entries.extend(topology::entries());
+    entries.extend(shapes::entries());
+    entries.extend(icons::entries());
+    entries.push(ScenarioEntry::new(
+        TrainMarkers::NAME,
+        TrainMarkers::DESCRIPTION,
+        |context| Box::new(TrainMarkers::new(context)),
+    ));
+    entries.extend(permutation::entries());
+    entries
+}
+
+/// Picks the registered scenarios with the given names, in the given order.
+///
+/// If no names are given, the scenarios [`ScenarioEntry::in_default`] are
+/// returned, i.e. the original reproduction of the bug.
+pub(crate) fn select(names: Option<&[String]>) -> Result<Vec<ScenarioEntry>, String> {
+    let registry = registry();
+    let Some(names) = names else {
+        return Ok(registry
+            .into_iter()
+            .filter(|entry| entry.in_default)
+            .collect());
+    };
+
+    names
+        .iter()
+        .map(|name| {
+            registry
+                .iter()
+                .find(|entry| entry.name == name.as_str())
+                .cloned()
+                .ok_or_else(|| {
+                    let available = registry
+                        .iter()
+                        .map(|entry| entry.name.as_ref())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("Unknown scenario `{name}`, available are: {available}")
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalSize;
+
+    #[test]
+    fn unsupported_scenarios_have_a_reason() {
+        let Some(context) = RenderContext::test_context(PhysicalSize::new(16, 16)) else {
+            return;
+        };
+
+        for entry in registry() {
+            assert_eq!(entry.unsupported_reason(&context), None, "{}", entry.name);
+        }
+
+        let mut requirements = Requirements::default();
+        requirements.limits.max_vertex_attributes = 1000;
+        let reason = requirements.unsupported_reason(&context.device).unwrap();
+        assert!(reason.contains("max_vertex_attributes"), "{reason}");
+    }
+
+    #[test]
+    fn selects_the_original_scenarios_by_default() {
+        let names = select(None)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, [OnlyPos::NAME, WithColor::NAME]);
+
+        let selected = select(Some(&["perm_dense".to_owned()])).unwrap();
+        assert_eq!(selected[0].name, "perm_dense");
+        assert!(select(Some(&["missing".to_owned()])).is_err());
+    }
+}
@@ -0,0 +1,369 @@
+//! Shader composition from the shared WGSL library in `src/shaders/`.
+//!
+//! Shader sources are run through a small preprocessor before being handed
+//! to naga and wgpu. It understands the following directives, each on a line
+//! of its own:
+//!
+//! - `#include "name.wgsl"` inserts a library module, at most once per shader
+//! - `#define NAME` defines a flag for the rest of the shader
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` keep or drop lines
+//!   depending on the defined flags
+//!
+//! Flags can also be given from Rust, e.g. `TARGET_IS_SRGB` by
+//! [`RenderContext::shader_source`].
+//!
+//! The shaders are embedded into the binary, unless the context has a
+//! `shader_dir` to load them from instead, see [`crate::hot_reload`].
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+use crate::color;
+use crate::RenderContext;
+
+/// The shared modules, by include name.
+const LIBRARY: &[(&str, &str)] = &[
+    ("color.wgsl", include_str!("shaders/color.wgsl")),
+    ("instance.wgsl", include_str!("shaders/instance.wgsl")),
+    ("view.wgsl", include_str!("shaders/view.wgsl")),
+];
+
+/// Directory of the library modules, relative to the shader directory.
+const LIBRARY_DIR: &str = "shaders";
+
+/// The defined preprocessor flags.
+pub(crate) type Defines = BTreeSet<String>;
+
+/// Looks up a module of the shared library.
+pub(crate) fn library_module(name: &str) -> Option<Cow<'static, str>> {
+    LIBRARY
+        .iter()
+        .find(|(module, _)| *module == name)
+        .map(|(_, source)| Cow::Borrowed(*source))
+}
+
+/// An invalid preprocessor directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PreprocessError {
+    /// The included module doesn't exist.
+    UnknownInclude {
+        line: usize,
+        name: String,
+    },
+    /// A directive without the expected argument, e.g. `#include` without a
+    /// quoted name.
+    MalformedDirective {
+        line: usize,
+        directive: String,
+    },
+    UnknownDirective {
+        line: usize,
+        directive: String,
+    },
+    /// An `#else` or `#endif` without an `#ifdef`.
+    UnmatchedConditional {
+        line: usize,
+    },
+    /// An `#ifdef` without `#endif`.
+    UnterminatedConditional,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownInclude { line, name } => {
+                write!(f, "line {line}: unknown include `{name}`")
+            }
+            Self::MalformedDirective { line, directive } => {
+                write!(f, "line {line}: malformed directive `{directive}`")
+            }
+            Self::UnknownDirective { line, directive } => {
+                write!(f, "line {line}: unknown directive `{directive}`")
+            }
+            Self::UnmatchedConditional { line } => {
+                write!(f, "line {line}: `#else` or `#endif` without `#ifdef`")
+            }
+            Self::UnterminatedConditional => write!(f, "`#ifdef` without `#endif`"),
+        }
+    }
+}
+
+/// Preprocesses `source`, resolving includes from the shared library.
+pub(crate) fn compose(source: &str, defines: &Defines) -> Result<String, PreprocessError> {
+    compose_with(source, defines, &library_module)
+}
+
+/// Preprocesses `source`, resolving includes with `resolve`.
+pub(crate) fn compose_with(
+    source: &str,
+    defines: &Defines,
+    resolve: &dyn Fn(&str) -> Option<Cow<'static, str>>,
+) -> Result<String, PreprocessError> {
+    let mut preprocessor = Preprocessor {
+        defines: defines.clone(),
+        included: BTreeSet::new(),
+        resolve,
+        output: String::new(),
+    };
+    preprocessor.process(source)?;
+    Ok(preprocessor.output)
+}
+
+struct Preprocessor<'a> {
+    defines: Defines,
+    /// Modules already included, which are skipped when included again.
+    included: BTreeSet<String>,
+    resolve: &'a dyn Fn(&str) -> Option<Cow<'static, str>>,
+    output: String,
+}
+
+impl Preprocessor<'_> {
+    fn process(&mut self, source: &str) -> Result<(), PreprocessError> {
+        // Whether the enclosing conditionals keep their lines, innermost last
+        let mut conditions: Vec<bool> = Vec::new();
+
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let active = conditions.iter().all(|&keep| keep);
+
+            let Some(directive) = text.trim_start().strip_prefix('#') else {
+                if active {
+                    self.output.push_str(text);
+                    self.output.push('\n');
+                }
+                continue;
+            };
+
+            let (keyword, argument) = directive
+                .trim()
+                .split_once(char::is_whitespace)
+                .map_or((directive.trim(), ""), |(k, a)| (k, a.trim()));
+            let malformed = || PreprocessError::MalformedDirective {
+                line,
+                directive: text.trim().to_owned(),
+            };
+
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    if argument.is_empty() {
+                        return Err(malformed());
+                    }
+                    let defined = self.defines.contains(argument);
+                    conditions.push(defined == (keyword == "ifdef"));
+                }
+                "else" => {
+                    let keep = conditions
+                        .last_mut()
+                        .ok_or(PreprocessError::UnmatchedConditional { line })?;
+                    *keep = !*keep;
+                }
+                "endif" => {
+                    conditions
+                        .pop()
+                        .ok_or(PreprocessError::UnmatchedConditional { line })?;
+                }
+                _ if !active => {}
+                "define" => {
+                    if argument.is_empty() {
+                        return Err(malformed());
+                    }
+                    self.defines.insert(argument.to_owned());
+                }
+                "include" => {
+                    let name = argument
+                        .strip_prefix('"')
+                        .and_then(|a| a.strip_suffix('"'))
+                        .ok_or_else(malformed)?;
+                    if !self.included.insert(name.to_owned()) {
+                        continue;
+                    }
+                    let module =
+                        (self.resolve)(name).ok_or_else(|| PreprocessError::UnknownInclude {
+                            line,
+                            name: name.to_owned(),
+                        })?;
+                    self.process(&module)?;
+                }
+                _ => {
+                    return Err(PreprocessError::UnknownDirective {
+                        line,
+                        directive: text.trim().to_owned(),
+                    })
+                }
+            }
+        }
+
+        if conditions.is_empty() {
+            Ok(())
+        } else {
+            Err(PreprocessError::UnterminatedConditional)
+        }
+    }
+}
+
+/// A shader source of a scenario.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ShaderFile {
+    /// Path relative to the shader directory, e.g. `only_pos/shader.wgsl`.
+    pub(crate) path: &'static str,
+    /// The source embedded into the binary.
+    pub(crate) embedded: &'static str,
+}
+
+/// A composed shader.
+#[derive(Debug, Clone)]
+pub(crate) struct LoadedShader {
+    pub(crate) source: String,
+    /// The files the shader was loaded from, including the included library
+    /// modules. Empty for embedded shaders.
+    pub(crate) files: Vec<PathBuf>,
+}
+
+/// Loads `file` and its includes from `dir`.
+fn load_from_dir(dir: &Path, file: &ShaderFile, defines: &Defines) -> Result<LoadedShader, String> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))
+    };
+
+    let path = dir.join(file.path);
+    let source = read(&path)?;
+    let files = RefCell::new(vec![path]);
+    let resolve = |name: &str| -> Option<Cow<'static, str>> {
+        let path = dir.join(LIBRARY_DIR).join(name);
+        let source = read(&path).map_err(|msg| log::error!("{msg}")).ok()?;
+        files.borrow_mut().push(path);
+        Some(Cow::Owned(source))
+    };
+    let source = compose_with(&source, defines, &resolve)
+        .map_err(|e| format!("Invalid shader {}: {e}", file.path))?;
+
+    Ok(LoadedShader {
+        source,
+        files: files.into_inner(),
+    })
+}
+
+/// Validates the module parsed from `source` with naga, as wgpu would.
+pub(crate) fn validate(module: &naga::Module, source: &str) -> Result<(), String> {
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(module)
+        .map(|_| ())
+        .map_err(|e| e.emit_to_string(source))
+}
+
+impl RenderContext {
+    /// The defines describing the render target.
+    pub(crate) fn shader_defines(&self) -> Defines {
+        let mut defines = Defines::new();
+        if color::is_srgb(self.swapchain_format) {
+            defines.insert("TARGET_IS_SRGB".to_owned());
+        }
+        defines
+    }
+
+    /// Preprocesses `source` for the render target.
+    pub(crate) fn shader_source(&self, source: &str) -> Result<String, PreprocessError> {
+        compose(source, &self.shader_defines())
+    }
+
+    /// Loads and preprocesses `file` for the render target, from the shader
+    /// directory if there is one.
+    pub(crate) fn load_shader(&self, file: &ShaderFile) -> Result<LoadedShader, String> {
+        let defines = self.shader_defines();
+        match &self.shader_dir {
+            Some(dir) => load_from_dir(dir, file, &defines),
+            None => Ok(LoadedShader {
+                source: compose(file.embedded, &defines)
+                    .map_err(|e| format!("Invalid shader {}: {e}", file.path))?,
+                files: Vec::new(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(name: &str) -> Option<Cow<'static, str>> {
+        match name {
+            "a.wgsl" => Some("#include \"b.wgsl\"\nfn a() {}".into()),
+            "b.wgsl" => Some("#include \"a.wgsl\"\nfn b() {}".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn includes_modules_once() {
+        let source = "#include \"a.wgsl\"\n#include \"b.wgsl\"\nfn main() {}";
+        let output = compose_with(source, &Defines::new(), &resolve).unwrap();
+        assert_eq!(output, "fn b() {}\nfn a() {}\nfn main() {}\n");
+
+        assert_eq!(
+            compose_with("#include \"c.wgsl\"", &Defines::new(), &resolve),
+            Err(PreprocessError::UnknownInclude {
+                line: 1,
+                name: "c.wgsl".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn evaluates_conditionals() {
+        let source = "\
+#ifdef A
+a
+#ifndef B
+not b
+#else
+b
+#endif
+#else
+not a
+#endif
+#define B
+#ifdef B
+defined b
+#endif";
+        let defines = Defines::from(["A".to_owned()]);
+        assert_eq!(
+            compose_with(source, &defines, &resolve).unwrap(),
+            "a\nnot b\ndefined b\n"
+        );
+        assert_eq!(
+            compose_with(source, &Defines::new(), &resolve).unwrap(),
+            "not a\ndefined b\n"
+        );
+
+        assert_eq!(
+            compose_with("#ifdef A\n", &Defines::new(), &resolve),
+            Err(PreprocessError::UnterminatedConditional)
+        );
+        assert_eq!(
+            compose_with("#endif\n", &Defines::new(), &resolve),
+            Err(PreprocessError::UnmatchedConditional { line: 1 })
+        );
+    }
+
+    #[test]
+    fn scenario_shaders_compose() {
+        for source in [
+            include_str!("only_pos/shader.wgsl"),
+            include_str!("with_color/shader.wgsl"),
+        ] {
+            for srgb in [false, true] {
+                let mut defines = Defines::new();
+                if srgb {
+                    defines.insert("TARGET_IS_SRGB".to_owned());
+                }
+                let source = compose(source, &defines).unwrap();
+                crate::reflect::parse_wgsl(&source).unwrap();
+            }
+        }
+    }
+}
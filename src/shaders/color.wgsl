@@ -0,0 +1,29 @@
+// Shared color-space conversions.
+//
+// Define `TARGET_IS_SRGB` if the render target encodes to sRGB on write, see
+// `color.rs`.
+
+/// Decodes an sRGB encoded color to linear.
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+    let low = srgb / 12.92;
+    let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
+/// Encodes a linear color as sRGB.
+fn linear_to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+/// Converts a linear color to the value to write to the render target.
+///
+/// sRGB targets encode on write, all others have to be written encoded.
+fn linear_to_target(linear: vec3<f32>) -> vec3<f32> {
+#ifdef TARGET_IS_SRGB
+    return linear;
+#else
+    return linear_to_srgb(linear);
+#endif
+}
@@ -0,0 +1,14 @@
+// The view uniform shared by all scenarios at group 0, see `context.rs`.
+
+/// View transformation
+struct View {
+    scale: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+/// Transforms a world position to clip space, keeping the aspect ratio.
+fn view_transform(world_position: vec3<f32>) -> vec4<f32> {
+    return vec4<f32>(world_position.xy * view.scale, world_position.z, 1.0);
+}
@@ -0,0 +1,39 @@
+//! Scenarios drawing the generated shapes of [`crate::mesh`].
+
+use wgpu::PrimitiveTopology;
+
+use crate::mesh::{circle, rect, ring, star, Shape};
+use crate::polygon::{row_entries, RowCase};
+use crate::scenario::ScenarioEntry;
+use crate::with_color::{self, PolygonVertex};
+
+/// The generated shapes.
+const CASES: &[RowCase<fn() -> Shape>] = &[
+    RowCase {
+        name: "shape_rect",
+        description: "Generated rectangle in a single color.",
+        spec: || rect(1.0, 0.6).with_color([0.9, 0.6, 0.1]),
+    },
+    RowCase {
+        name: "shape_circle",
+        description: "Generated circle with the color wheel gradient.",
+        spec: || circle(0.5).with_gradient(),
+    },
+    RowCase {
+        name: "shape_ring",
+        description: "Generated ring with the color wheel gradient.",
+        spec: || ring(0.3, 0.5, 32).with_gradient(),
+    },
+    RowCase {
+        name: "shape_star",
+        description: "Generated five-pointed star in a single color.",
+        spec: || star(5, 0.2, 0.5).with_color([1.0, 0.85, 0.0]),
+    },
+];
+
+/// Returns the registry entries of all shapes.
+pub(crate) fn entries() -> Vec<ScenarioEntry> {
+    row_entries(CASES, with_color::SHADER, 0.55, 0.18, |case| {
+        (case.spec)().geometry::<PolygonVertex>(PrimitiveTopology::TriangleList)
+    })
+}
@@ -0,0 +1,283 @@
+//! Selection of the surface format and present mode.
+//!
+//! The [`SurfacePolicy`] ranks the formats and present modes supported by the
+//! surface. It can prefer, avoid or forbid sRGB formats and force a specific
+//! format or present mode. If a forced choice isn't supported, the policy
+//! falls back to the ranking and logs why.
+
+use log::{info, warn};
+use wgpu::{PresentMode, TextureFormat};
+
+/// How to treat sRGB surface formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SrgbPreference {
+    /// Take an sRGB format over an otherwise equal linear one.
+    Prefer,
+    /// Take a linear format over an otherwise equal sRGB one.
+    Avoid,
+    /// Never take an sRGB format, unless the surface supports nothing else.
+    Forbid,
+}
+
+impl Default for SrgbPreference {
+    fn default() -> Self {
+        // The web canvas is usually not sRGB, so sRGB formats are only
+        // emulated there.
+        if cfg!(target_arch = "wasm32") {
+            Self::Avoid
+        } else {
+            Self::Prefer
+        }
+    }
+}
+
+impl SrgbPreference {
+    pub(crate) fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "prefer" => Ok(Self::Prefer),
+            "avoid" => Ok(Self::Avoid),
+            "forbid" => Ok(Self::Forbid),
+            _ => Err(format!(
+                "Unknown sRGB preference `{name}`, available: prefer, avoid, forbid"
+            )),
+        }
+    }
+}
+
+/// How to choose the surface format and present mode.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SurfacePolicy {
+    pub(crate) srgb: SrgbPreference,
+    /// Format to take if the surface supports it.
+    pub(crate) format: Option<TextureFormat>,
+    /// Present mode to take if the surface supports it.
+    pub(crate) present_mode: Option<PresentMode>,
+}
+
+/// The formats commonly supported by surfaces, which can be forced by name.
+const SURFACE_FORMATS: [TextureFormat; 6] = [
+    TextureFormat::Bgra8Unorm,
+    TextureFormat::Bgra8UnormSrgb,
+    TextureFormat::Rgba8Unorm,
+    TextureFormat::Rgba8UnormSrgb,
+    TextureFormat::Rgb10a2Unorm,
+    TextureFormat::Rgba16Float,
+];
+
+const PRESENT_MODES: [PresentMode; 6] = [
+    PresentMode::AutoVsync,
+    PresentMode::AutoNoVsync,
+    PresentMode::Fifo,
+    PresentMode::FifoRelaxed,
+    PresentMode::Immediate,
+    PresentMode::Mailbox,
+];
+
+/// Finds the value whose `Debug` name matches, ignoring case, `-` and `_`.
+fn find_by_name<T: Copy + std::fmt::Debug>(values: &[T], name: &str) -> Option<T> {
+    let normalize = |s: &str| s.replace(['-', '_'], "").to_lowercase();
+    values
+        .iter()
+        .copied()
+        .find(|value| normalize(&format!("{value:?}")) == normalize(name))
+}
+
+pub(crate) fn parse_format(name: &str) -> Result<TextureFormat, String> {
+    find_by_name(&SURFACE_FORMATS, name)
+        .ok_or_else(|| format!("Unknown surface format `{name}`, available: {SURFACE_FORMATS:?}"))
+}
+
+pub(crate) fn parse_present_mode(name: &str) -> Result<PresentMode, String> {
+    find_by_name(&PRESENT_MODES, name)
+        .ok_or_else(|| format!("Unknown present mode `{name}`, available: {PRESENT_MODES:?}"))
+}
+
+impl SurfacePolicy {
+    /// Chooses one of the formats supported by the surface.
+    ///
+    /// Returns `None` if there are none.
+    pub(crate) fn choose_format(&self, supported: &[TextureFormat]) -> Option<TextureFormat> {
+        if let Some(format) = self.format {
+            if supported.contains(&format) {
+                info!("Using the forced surface format {format:?}");
+                return Some(format);
+            }
+            warn!("Forced surface format {format:?} is not supported by the surface (supported: {supported:?}), falling back");
+        }
+
+        let mut candidates = supported.to_vec();
+        if self.srgb == SrgbPreference::Forbid {
+            candidates.retain(|format| !format.describe().srgb);
+            if candidates.is_empty() {
+                warn!(
+                    "The surface only supports sRGB formats ({supported:?}), ignoring the sRGB ban"
+                );
+                candidates = supported.to_vec();
+            }
+        }
+
+        // The first of the highest priority, as the surface lists its
+        // preferred formats first
+        let format = candidates
+            .iter()
+            .copied()
+            .rev()
+            .max_by_key(|format| self.format_priority(*format))?;
+        info!("Chose surface format {format:?} out of {supported:?}");
+        Some(format)
+    }
+
+    /// Ranks formats by the number of components, then by the sRGB
+    /// preference, then 8-bit unorm formats over wider ones, which are
+    /// usually float and only offered for HDR. Formats which can't be
+    /// rendered to are ranked last.
+    fn format_priority(&self, format: TextureFormat) -> u32 {
+        let describe = format.describe();
+        let renderable = matches!(describe.sample_type, wgpu::TextureSampleType::Float { .. })
+            && describe
+                .guaranteed_format_features
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT);
+        if !renderable {
+            return 0;
+        }
+
+        let srgb_bonus = match self.srgb {
+            SrgbPreference::Prefer => describe.srgb,
+            SrgbPreference::Avoid | SrgbPreference::Forbid => !describe.srgb,
+        };
+        let unorm8 = matches!(
+            format,
+            TextureFormat::Rgba8Unorm
+                | TextureFormat::Rgba8UnormSrgb
+                | TextureFormat::Bgra8Unorm
+                | TextureFormat::Bgra8UnormSrgb
+        );
+        describe.components as u32 * 4 + srgb_bonus as u32 * 2 + unorm8 as u32
+    }
+
+    /// Chooses one of the present modes supported by the surface.
+    ///
+    /// Returns `None` if there are none.
+    pub(crate) fn choose_present_mode(&self, supported: &[PresentMode]) -> Option<PresentMode> {
+        if let Some(present_mode) = self.present_mode {
+            if supported.contains(&present_mode) {
+                info!("Using the forced present mode {present_mode:?}");
+                return Some(present_mode);
+            }
+            warn!("Forced present mode {present_mode:?} is not supported by the surface (supported: {supported:?}), falling back");
+        }
+
+        supported
+            .iter()
+            .copied()
+            .max_by_key(|present_mode| present_mode_priority(*present_mode))
+    }
+}
+
+fn present_mode_priority(present_mode: PresentMode) -> u32 {
+    match present_mode {
+        // Fifo guarantees no tearing and keeps the framerate at the monitor refresh rate.
+        PresentMode::Fifo => 10,
+        // Should not tear and keeps the framerate at the monitor refresh rate.
+        // Tears if the framerate is too low.
+        PresentMode::FifoRelaxed => 9,
+        // Same as above.
+        PresentMode::AutoVsync => 8,
+        // No tearing, but framerate can be higher than the monitor refresh rate.
+        PresentMode::Mailbox => 7,
+        // No vsync at all.
+        PresentMode::Immediate => 6,
+        // Goes through most modes above
+        PresentMode::AutoNoVsync => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUPPORTED: &[TextureFormat] = &[
+        TextureFormat::Bgra8UnormSrgb,
+        TextureFormat::Bgra8Unorm,
+        TextureFormat::Rgba16Float,
+    ];
+
+    #[test]
+    fn ranks_formats_by_srgb_preference() {
+        let choose = |srgb| {
+            SurfacePolicy {
+                srgb,
+                ..Default::default()
+            }
+            .choose_format(SUPPORTED)
+        };
+        assert_eq!(
+            choose(SrgbPreference::Prefer),
+            Some(TextureFormat::Bgra8UnormSrgb)
+        );
+        assert_eq!(
+            choose(SrgbPreference::Avoid),
+            Some(TextureFormat::Bgra8Unorm)
+        );
+
+        let only_srgb = [TextureFormat::Bgra8UnormSrgb];
+        let forbid = SurfacePolicy {
+            srgb: SrgbPreference::Forbid,
+            ..Default::default()
+        };
+        assert_eq!(forbid.choose_format(&only_srgb), Some(only_srgb[0]));
+    }
+
+    #[test]
+    fn ranks_unorm_over_float_formats() {
+        // Listed first by the surface, but only an equal match under Avoid
+        let supported = [
+            TextureFormat::Rgba16Float,
+            TextureFormat::Bgra8UnormSrgb,
+            TextureFormat::Bgra8Unorm,
+        ];
+        for srgb in [SrgbPreference::Avoid, SrgbPreference::Forbid] {
+            let policy = SurfacePolicy {
+                srgb,
+                ..Default::default()
+            };
+            assert_eq!(
+                policy.choose_format(&supported),
+                Some(TextureFormat::Bgra8Unorm),
+                "{srgb:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn forced_choices_fall_back() {
+        let policy = SurfacePolicy {
+            srgb: SrgbPreference::Prefer,
+            format: Some(TextureFormat::Rgba8Unorm),
+            present_mode: Some(PresentMode::Mailbox),
+        };
+        assert_eq!(
+            policy.choose_format(SUPPORTED),
+            Some(TextureFormat::Bgra8UnormSrgb)
+        );
+        assert_eq!(
+            policy.choose_present_mode(&[PresentMode::Immediate, PresentMode::Fifo]),
+            Some(PresentMode::Fifo)
+        );
+        assert_eq!(
+            policy.choose_present_mode(&[PresentMode::Mailbox, PresentMode::Fifo]),
+            Some(PresentMode::Mailbox)
+        );
+    }
+
+    #[test]
+    fn parses_names() {
+        assert_eq!(
+            parse_format("bgra8unorm-srgb"),
+            Ok(TextureFormat::Bgra8UnormSrgb)
+        );
+        assert_eq!(parse_present_mode("auto_vsync"), Ok(PresentMode::AutoVsync));
+        assert!(parse_format("r8unorm").is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Sweep mode, running the scenarios on every available adapter.
+//!
+//! Each adapter gets a headless device, on which every scenario is built and
+//! rendered once, capturing its errors in the [`crate::errors::ErrorLog`].
+//! As the bug only shows up with several pipelines in the shared render
+//! bundle, all scenarios are then rendered together in one more frame, the
+//! [`ALL`] row. The outcomes are printed as a table, so it's easy to tell
+//! which backends exhibit the bug.
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use winit::dpi::PhysicalSize;
+
+use crate::config::RenderConfig;
+use crate::options::Options;
+use crate::scenario::ScenarioEntry;
+use crate::RenderContext;
+
+/// Size of the offscreen target of each adapter.
+const SIZE: PhysicalSize<u32> = PhysicalSize::new(256, 256);
+
+/// Scenario column of the frame rendering all scenarios together.
+pub(crate) const ALL: &str = "<all>";
+
+/// Outcome of a single scenario on a single adapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Pass,
+    /// wgpu reported a validation error.
+    ValidationError(String),
+    /// Building or rendering the scenario panicked.
+    Fail(String),
+    /// The device doesn't support the scenario.
+    Skipped(String),
+}
+
+impl Outcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::ValidationError(_) => "validation error",
+            Self::Fail(_) => "fail",
+            Self::Skipped(_) => "skipped",
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pass => write!(f, "pass"),
+            Self::ValidationError(msg) | Self::Fail(msg) | Self::Skipped(msg) => {
+                // The first line is enough for the table
+                let msg = msg.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+                write!(f, "{}: {}", self.label(), msg.trim())
+            }
+        }
+    }
+}
+
+/// A row of the sweep table.
+#[derive(Debug, Clone)]
+pub(crate) struct SweepResult {
+    pub(crate) adapter: String,
+    pub(crate) backend: wgpu::Backend,
+    pub(crate) scenario: String,
+    pub(crate) outcome: Outcome,
+}
+
+/// Runs the scenarios on all adapters allowed by the config.
+pub(crate) fn sweep(
+    options: &Options,
+    config: &RenderConfig,
+    scenarios: &[ScenarioEntry],
+) -> Vec<SweepResult> {
+    let instance = config.create_instance();
+    let mut results = Vec::new();
+
+    for adapter in instance.enumerate_adapters(config.backends) {
+        let info = adapter.get_info();
+        if !config.matches_name(&info) {
+            continue;
+        }
+        log::info!("Sweeping {info:?}");
+
+        let mut push = |scenario: &str, outcome| {
+            results.push(SweepResult {
+                adapter: info.name.clone(),
+                backend: info.backend,
+                scenario: scenario.to_owned(),
+                outcome,
+            })
+        };
+
+        let context = catch_panic(|| {
+            pollster::block_on(RenderContext::from_adapter(
+                &adapter,
+                config.profile,
+                wgpu::TextureFormat::Rgba8Unorm,
+                SIZE,
+            ))
+        });
+        let mut context = match context {
+            Ok(context) => context,
+            Err(msg) => {
+                for entry in scenarios {
+                    push(&entry.name, Outcome::Fail(msg.clone()));
+                }
+                if scenarios.len() > 1 {
+                    push(ALL, Outcome::Fail(msg));
+                }
+                continue;
+            }
+        };
+        context.compact_locations = options.compact_locations;
+
+        for entry in scenarios {
+            push(
+                &entry.name,
+                run_scenarios(&context, std::slice::from_ref(entry)),
+            );
+        }
+        if scenarios.len() > 1 {
+            push(ALL, run_scenarios(&context, scenarios));
+        }
+    }
+
+    results
+}
+
+/// Builds the supported scenarios and renders them together in one frame.
+///
+/// Skipped if none of the scenarios is supported.
+fn run_scenarios(context: &RenderContext, entries: &[ScenarioEntry]) -> Outcome {
+    let mut supported = Vec::new();
+    let mut reasons = Vec::new();
+    for entry in entries {
+        match entry.unsupported_reason(context) {
+            Some(reason) => reasons.push(reason),
+            None => supported.push(entry),
+        }
+    }
+    if supported.is_empty() {
+        return Outcome::Skipped(reasons.join("; "));
+    }
+    context.errors.take();
+
+    let rendered = catch_panic(|| {
+        let mut scenarios = supported
+            .iter()
+            .map(|entry| entry.build(context))
+            .collect::<Vec<_>>();
+        for scenario in &mut scenarios {
+            scenario.update(context);
+        }
+        context.render_frame(&scenarios);
+    });
+    context.device.poll(wgpu::Maintain::Wait);
+
+    let errors = context.errors.take();
+    match (rendered, errors.first()) {
+        (Err(msg), _) => Outcome::Fail(msg),
+        (Ok(()), Some(error)) => Outcome::ValidationError(error.to_string()),
+        (Ok(()), None) => Outcome::Pass,
+    }
+}
+
+/// Runs `f`, turning a panic into its message.
+fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        if let Some(msg) = payload.downcast_ref::<&str>() {
+            (*msg).to_owned()
+        } else if let Some(msg) = payload.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            "unknown panic".to_owned()
+        }
+    })
+}
+
+/// Formats the results as a table with one row per (adapter, scenario).
+pub(crate) fn format_table(results: &[SweepResult]) -> String {
+    let header = ["Adapter", "Backend", "Scenario", "Result"];
+    let rows = results
+        .iter()
+        .map(|r| {
+            [
+                r.adapter.clone(),
+                format!("{:?}", r.backend),
+                r.scenario.clone(),
+                r.outcome.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let format_row = |cells: &[&str]| {
+        let line = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        line.trim_end().to_owned()
+    };
+
+    let mut table = format_row(&header);
+    table.push('\n');
+    table.push_str(&format_row(
+        &widths.map(|w| "-".repeat(w)).each_ref().map(String::as_str),
+    ));
+    for row in &rows {
+        table.push('\n');
+        table.push_str(&format_row(&row.each_ref().map(String::as_str)));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario;
+
+    #[test]
+    fn renders_all_scenarios_together() {
+        let scenarios = scenario::select(None).unwrap();
+        let results = sweep(&Options::default(), &RenderConfig::default(), &scenarios);
+        for adapter in results.chunks(scenarios.len() + 1) {
+            let names = adapter
+                .iter()
+                .map(|r| r.scenario.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(names, ["only_pos", "with_color", ALL]);
+        }
+    }
+
+    #[test]
+    fn table_lists_every_result() {
+        let results = [
+            SweepResult {
+                adapter: "llvmpipe".to_owned(),
+                backend: wgpu::Backend::Gl,
+                scenario: "only_pos".to_owned(),
+                outcome: Outcome::Fail("GL_INVALID_OPERATION\nmore".to_owned()),
+            },
+            SweepResult {
+                adapter: "llvmpipe".to_owned(),
+                backend: wgpu::Backend::Vulkan,
+                scenario: "with_color".to_owned(),
+                outcome: Outcome::Pass,
+            },
+        ];
+
+        let table = format_table(&results);
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "Adapter  | Backend | Scenario   | Result",
+                "-------- | ------- | ---------- | --------------------------",
+                "llvmpipe | Gl      | only_pos   | fail: GL_INVALID_OPERATION",
+                "llvmpipe | Vulkan  | with_color | pass",
+            ]
+        );
+    }
+}
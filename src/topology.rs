@@ -0,0 +1,49 @@
+//! Scenarios drawing the same hexagon with each primitive topology.
+//!
+//! The list and strip variants of the triangles (and lines) cover exactly the
+//! same shape, so a backend treating strips differently from lists shows up
+//! as differing hexagons in the row at the top.
+
+use wgpu::PrimitiveTopology;
+
+use crate::mesh::regular_polygon;
+use crate::polygon::{row_entries, RowCase};
+use crate::scenario::ScenarioEntry;
+use crate::with_color::{self, PolygonVertex, HEXAGON_RADIUS};
+
+const CASES: &[RowCase<PrimitiveTopology>] = &[
+    RowCase {
+        name: "topology_triangle_list",
+        description: "Filled hexagon as a triangle list of four triangles.",
+        spec: PrimitiveTopology::TriangleList,
+    },
+    RowCase {
+        name: "topology_triangle_strip",
+        description: "Filled hexagon as a triangle strip of four triangles.",
+        spec: PrimitiveTopology::TriangleStrip,
+    },
+    RowCase {
+        name: "topology_line_list",
+        description: "Hexagon outline as a line list.",
+        spec: PrimitiveTopology::LineList,
+    },
+    RowCase {
+        name: "topology_line_strip",
+        description: "Hexagon outline as a line strip.",
+        spec: PrimitiveTopology::LineStrip,
+    },
+    RowCase {
+        name: "topology_point_list",
+        description: "Hexagon corners as a point list.",
+        spec: PrimitiveTopology::PointList,
+    },
+];
+
+/// Returns the registry entries of all topology variants.
+pub(crate) fn entries() -> Vec<ScenarioEntry> {
+    row_entries(CASES, with_color::SHADER, 0.8, 0.09, |case| {
+        regular_polygon(6, HEXAGON_RADIUS)
+            .with_gradient()
+            .geometry::<PolygonVertex>(case.spec)
+    })
+}
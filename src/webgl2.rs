@@ -0,0 +1,311 @@
+//! Static WebGL2 compatibility checks for render pipelines.
+//!
+//! The GL backend has a few known hazards regarding vertex attributes, which
+//! only show up as an `INVALID_OPERATION` in the browser console. These
+//! checks run on the pipeline descriptor and the naga module of its shader,
+//! so they also work on CI without a browser or GPU.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+/// A potential problem of a pipeline on the WebGL2 backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Webgl2Hazard {
+    /// The shader module failed naga validation.
+    InvalidModule(String),
+    /// The vertex entry point does not exist.
+    MissingEntryPoint(String),
+    /// The attribute locations do not form a contiguous range starting at 0.
+    NonContiguousLocations { missing: Vec<u32> },
+    /// An attribute is provided, but never read by the shader.
+    UnusedAttribute { location: u32 },
+    /// More attributes than WebGL2 guarantees.
+    TooManyAttributes { count: u32, max: u32 },
+    /// More vertex buffers than WebGL2 guarantees.
+    TooManyBuffers { count: u32, max: u32 },
+}
+
+impl fmt::Display for Webgl2Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidModule(msg) => write!(f, "shader module is invalid: {msg}"),
+            Self::MissingEntryPoint(name) => write!(f, "no vertex entry point `{name}`"),
+            Self::NonContiguousLocations { missing } => {
+                write!(
+                    f,
+                    "attribute locations are not contiguous, missing {missing:?}"
+                )
+            }
+            Self::UnusedAttribute { location } => {
+                write!(
+                    f,
+                    "attribute at location {location} is never read by the shader"
+                )
+            }
+            Self::TooManyAttributes { count, max } => {
+                write!(
+                    f,
+                    "{count} vertex attributes exceed the WebGL2 limit of {max}"
+                )
+            }
+            Self::TooManyBuffers { count, max } => {
+                write!(f, "{count} vertex buffers exceed the WebGL2 limit of {max}")
+            }
+        }
+    }
+}
+
+/// Reports the known WebGL2 hazards of a pipeline.
+///
+/// The `module` must be the naga module of the vertex shader of `descriptor`.
+pub(crate) fn validate_for_webgl2(
+    descriptor: &wgpu::RenderPipelineDescriptor,
+    module: &naga::Module,
+) -> Vec<Webgl2Hazard> {
+    validate_vertex_state(
+        descriptor.vertex.buffers,
+        descriptor.vertex.entry_point,
+        module,
+    )
+}
+
+/// Reports the known WebGL2 hazards of the vertex state of a pipeline.
+///
+/// Unlike [`validate_for_webgl2`], this needs no `wgpu::ShaderModule` and
+/// thus no device.
+pub(crate) fn validate_vertex_state(
+    buffers: &[wgpu::VertexBufferLayout],
+    entry_point: &str,
+    module: &naga::Module,
+) -> Vec<Webgl2Hazard> {
+    let mut hazards = Vec::new();
+    let limits = wgpu::Limits::downlevel_webgl2_defaults();
+
+    let locations = buffers
+        .iter()
+        .flat_map(|buffer| buffer.attributes)
+        .map(|attr| attr.shader_location)
+        .collect::<BTreeSet<_>>();
+
+    let buffer_count = buffers.len() as u32;
+    if buffer_count > limits.max_vertex_buffers {
+        hazards.push(Webgl2Hazard::TooManyBuffers {
+            count: buffer_count,
+            max: limits.max_vertex_buffers,
+        });
+    }
+
+    let attribute_count = locations.len() as u32;
+    if attribute_count > limits.max_vertex_attributes {
+        hazards.push(Webgl2Hazard::TooManyAttributes {
+            count: attribute_count,
+            max: limits.max_vertex_attributes,
+        });
+    }
+
+    if let Some(&max) = locations.iter().next_back() {
+        let missing = (0..max)
+            .filter(|l| !locations.contains(l))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            hazards.push(Webgl2Hazard::NonContiguousLocations { missing });
+        }
+    }
+
+    match read_locations(module, entry_point) {
+        Ok(read) => {
+            hazards.extend(
+                locations
+                    .difference(&read)
+                    .map(|&location| Webgl2Hazard::UnusedAttribute { location }),
+            );
+        }
+        Err(hazard) => hazards.push(hazard),
+    }
+
+    hazards
+}
+
+/// Returns the input locations the vertex entry point actually reads.
+///
+/// Inputs declared, but never accessed, are dropped by the GLSL compiler, so
+/// their attributes become inactive on the GL backend.
+fn read_locations(module: &naga::Module, entry_point: &str) -> Result<BTreeSet<u32>, Webgl2Hazard> {
+    use naga::Expression;
+
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(module)
+        .map_err(|e| Webgl2Hazard::InvalidModule(e.to_string()))?;
+
+    let (index, entry_point) = module
+        .entry_points
+        .iter()
+        .enumerate()
+        .find(|(_, ep)| ep.name == entry_point && ep.stage == naga::ShaderStage::Vertex)
+        .ok_or_else(|| Webgl2Hazard::MissingEntryPoint(entry_point.to_owned()))?;
+    let function = &entry_point.function;
+    let function_info = info.get_entry_point(index);
+
+    // Members of the struct argument with the given index
+    let struct_members = |arg_index: u32| {
+        let arg = &function.arguments[arg_index as usize];
+        match &module.types[arg.ty].inner {
+            naga::TypeInner::Struct { members, .. } => members.as_slice(),
+            _ => &[],
+        }
+    };
+
+    // Find the expressions of the arguments and the member accesses on them
+    let mut read = BTreeSet::new();
+    for (handle, expr) in function.expressions.iter() {
+        if function_info[handle].ref_count == 0 {
+            continue;
+        }
+        match *expr {
+            Expression::FunctionArgument(arg_index) => {
+                let arg = &function.arguments[arg_index as usize];
+                if let Some(naga::Binding::Location { location, .. }) = arg.binding {
+                    read.insert(location);
+                    continue;
+                }
+
+                // A struct used as a whole (e.g. passed to a function) reads
+                // all of its members
+                let accesses = function
+                    .expressions
+                    .iter()
+                    .filter(|(_, e)| match **e {
+                        Expression::AccessIndex { base, .. } => base == handle,
+                        _ => false,
+                    })
+                    .count();
+                if function_info[handle].ref_count > accesses {
+                    read.extend(struct_members(arg_index).iter().filter_map(member_location));
+                }
+            }
+            Expression::AccessIndex { base, index } => {
+                if let Expression::FunctionArgument(arg_index) = function.expressions[base] {
+                    let member = struct_members(arg_index).get(index as usize);
+                    read.extend(member.and_then(member_location));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(read)
+}
+
+fn member_location(member: &naga::StructMember) -> Option<u32> {
+    match member.binding {
+        Some(naga::Binding::Location { location, .. }) => Some(location),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader;
+
+    const INSTANCE_ATTR: &[wgpu::VertexAttribute] =
+        &wgpu::vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4];
+
+    fn layout(
+        attributes: &[wgpu::VertexAttribute],
+        step_mode: wgpu::VertexStepMode,
+    ) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: attributes
+                .iter()
+                .map(|attr| attr.offset + attr.format.size())
+                .max()
+                .unwrap_or(0),
+            step_mode,
+            attributes,
+        }
+    }
+
+    fn check(source: &str, buffers: &[wgpu::VertexBufferLayout]) -> Vec<Webgl2Hazard> {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        validate_vertex_state(buffers, "vs_main", &module)
+    }
+
+    #[test]
+    fn only_pos_has_gap() {
+        let vertex = wgpu::vertex_attr_array![0 => Float32x3];
+        let hazards = check(
+            &shader::compose(include_str!("only_pos/shader.wgsl"), &Default::default()).unwrap(),
+            &[
+                layout(&vertex, wgpu::VertexStepMode::Vertex),
+                layout(INSTANCE_ATTR, wgpu::VertexStepMode::Instance),
+            ],
+        );
+        assert_eq!(
+            hazards,
+            [Webgl2Hazard::NonContiguousLocations { missing: vec![1] }]
+        );
+    }
+
+    #[test]
+    fn with_color_is_clean() {
+        let vertex = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+        let hazards = check(
+            &shader::compose(include_str!("with_color/shader.wgsl"), &Default::default()).unwrap(),
+            &[
+                layout(&vertex, wgpu::VertexStepMode::Vertex),
+                layout(INSTANCE_ATTR, wgpu::VertexStepMode::Instance),
+            ],
+        );
+        assert_eq!(hazards, []);
+    }
+
+    #[test]
+    fn unused_attribute() {
+        let source = "
+            struct VertexInput {
+                @location(0) position: vec3<f32>,
+                @location(1) color: vec3<f32>,
+            };
+            @vertex
+            fn vs_main(vertex: VertexInput) -> @builtin(position) vec4<f32> {
+                return vec4<f32>(vertex.position, 1.0);
+            }
+        ";
+        let vertex = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+        let hazards = check(source, &[layout(&vertex, wgpu::VertexStepMode::Vertex)]);
+        assert_eq!(hazards, [Webgl2Hazard::UnusedAttribute { location: 1 }]);
+    }
+
+    #[test]
+    fn too_many_attributes() {
+        let inputs = (0..17)
+            .map(|l| format!("@location({l}) a{l}: f32,"))
+            .collect::<String>();
+        let sum = (0..17).map(|l| format!(" + v.a{l}")).collect::<String>();
+        let source = format!(
+            "struct VertexInput {{ {inputs} }};
+            @vertex
+            fn vs_main(v: VertexInput) -> @builtin(position) vec4<f32> {{
+                return vec4<f32>(0.0{sum});
+            }}"
+        );
+        let attributes = (0..17)
+            .map(|l| wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32,
+                offset: l as u64 * 4,
+                shader_location: l,
+            })
+            .collect::<Vec<_>>();
+        let hazards = check(
+            &source,
+            &[layout(&attributes, wgpu::VertexStepMode::Vertex)],
+        );
+        assert_eq!(
+            hazards,
+            [Webgl2Hazard::TooManyAttributes { count: 17, max: 16 }]
+        );
+    }
+}